@@ -7,6 +7,7 @@ pub fn main() -> iced::Result {
 #[derive(Default)]
 struct Counter {
     value: i32,
+    exiting: bool,
     increment_button: button::State,
     decrement_button: button::State,
 }
@@ -15,6 +16,14 @@ struct Counter {
 enum Message {
     IncrementPressed,
     DecrementPressed,
+    SyncPressed,
+    Synced(i32),
+    QuitPressed,
+}
+
+/// Fetches the value other devices have counted to
+async fn fetch_value() -> i32 {
+    0
 }
 
 impl Application for Counter {
@@ -38,10 +47,23 @@ impl Application for Counter {
             Message::DecrementPressed => {
                 self.value -= 1;
             }
+            Message::SyncPressed => {
+                return Command::perform(fetch_value(), Message::Synced);
+            }
+            Message::Synced(value) => {
+                self.value = value;
+            }
+            Message::QuitPressed => {
+                self.exiting = true;
+            }
         }
         Command::none()
     }
 
+    fn should_exit(&self) -> bool {
+        self.exiting
+    }
+
     fn view(&mut self) -> Element<Message> {
         Column::new()
             .padding(20)
@@ -62,11 +84,19 @@ impl Application for Counter {
 mod test {
     use super::*;
     use iced_test::{
-        runners::{execute_iced_trace, execute_message_trace},
-        Screenshot, TraceEvent,
+        expect_message,
+        rendering::screenshot::{metadata, CURSOR_COLOR},
+        runners::{
+            execute_iced_trace, execute_iced_trace_matrix, execute_message_trace, CommandStub,
+            ExitPolicy, MatrixRun, MessageLog, MessageSource, Runner, WindowConfig, WindowHistory,
+        },
+        LayoutSnapshot, Mask, Recording, Screenshot, Selector, TraceEvent,
     };
 
+    use std::cell::RefCell;
     use std::path::PathBuf;
+    use std::rc::Rc;
+    use std::time::Duration;
     #[test]
     fn simple_increment_test() {
         let message_trace = vec![Message::IncrementPressed, Message::IncrementPressed];
@@ -100,4 +130,321 @@ mod test {
         let app_state: Counter = execute_iced_trace((), message_trace);
         assert_eq!(app_state.value, 2)
     }
+
+    #[test]
+    fn buttons_fit_window() {
+        let trace = vec![
+            TraceEvent::Message(Message::IncrementPressed),
+            TraceEvent::LayoutCheck(Box::new(|layout: &LayoutSnapshot| {
+                let buttons = [
+                    Selector::text("Increment").parent(),
+                    Selector::text("Decrement").parent(),
+                ];
+                layout.is_inside_window(&buttons[1])
+                    && layout.none_overlap(&buttons)
+                    && !layout.is_text_clipped("Decrement")
+            })),
+        ];
+        let app_state: Counter = execute_iced_trace((), trace);
+        assert_eq!(app_state.value, 1)
+    }
+
+    #[test]
+    fn buttons_fit_every_window() {
+        let matrix = [
+            WindowConfig::new((390, 844)).scale_factor(3.0).name("phone"),
+            WindowConfig::new((1280, 800)).name("desktop"),
+        ];
+        let report = execute_iced_trace_matrix(
+            (),
+            |_| {
+                vec![
+                    TraceEvent::Message(Message::DecrementPressed),
+                    TraceEvent::LayoutCheck(Box::new(|layout: &LayoutSnapshot| {
+                        layout.is_inside_window(&Selector::text("Decrement").parent())
+                    })),
+                    TraceEvent::TakeScreenshot(PathBuf::from(format!(
+                        "{}/golden/decrement_ss.png",
+                        env!("CARGO_MANIFEST_DIR")
+                    ))),
+                ]
+            },
+            &matrix,
+        );
+        for (_, app_state) in report.unwrap_all() {
+            assert_eq!(app_state.value, -1)
+        }
+    }
+
+    #[test]
+    fn goldens_per_configuration() {
+        let matrix = [
+            WindowConfig::new((390, 844)).scale_factor(3.0).name("phone"),
+            WindowConfig::new((1280, 800)).name("desktop"),
+        ];
+        let path = PathBuf::from(format!(
+            "{}/golden/matrix_ss.png",
+            env!("CARGO_MANIFEST_DIR")
+        ));
+        let report = execute_iced_trace_matrix(
+            (),
+            |config: &WindowConfig| {
+                let golden = config.golden_path(&path);
+                let size = format!("{}x{}", config.size.0, config.size.1);
+                vec![
+                    TraceEvent::TakeScreenshot(path.clone()),
+                    TraceEvent::Message(Message::IncrementPressed),
+                    //the golden of this configuration has its size, but not the new value
+                    TraceEvent::CheckScreenshot(Box::new(move |ss: Screenshot| {
+                        let golden = Screenshot::from_png(&golden).unwrap();
+                        golden.metadata().get(metadata::WINDOW_SIZE) == Some(&size)
+                            && golden.dimensions() == ss.dimensions()
+                            && golden != ss
+                    })),
+                ]
+            },
+            &matrix,
+        );
+        let labels: Vec<String> = report.runs.iter().map(MatrixRun::label).collect();
+        assert_eq!(labels, vec![String::from("phone"), String::from("desktop")]);
+        let _ = report.unwrap_all();
+    }
+
+    #[test]
+    fn record_increments() {
+        let trace = vec![
+            TraceEvent::Message(Message::IncrementPressed),
+            TraceEvent::Duration(Duration::from_millis(200)),
+            TraceEvent::Message(Message::IncrementPressed),
+            TraceEvent::Message(Message::DecrementPressed),
+        ];
+        let app_state: Counter = Runner::new()
+            .record(Recording::every_step(format!(
+                "{}/golden/increments.png",
+                env!("CARGO_MANIFEST_DIR")
+            )))
+            .execute((), trace);
+        assert_eq!(app_state.value, 1)
+    }
+
+    #[test]
+    fn masked_value_matches() {
+        //the first capture, at a different value, is the reference of the second one
+        let reference: Rc<RefCell<Option<Screenshot>>> = Rc::default();
+        let check_masked = || {
+            let reference = Rc::clone(&reference);
+            TraceEvent::CheckScreenshot(Box::new(move |ss: Screenshot| {
+                match reference.borrow_mut().replace(ss.clone()) {
+                    Some(reference) => ss == reference,
+                    None => true,
+                }
+            }))
+        };
+        let unmasked: Rc<RefCell<Option<Screenshot>>> = Rc::default();
+        let check_unmasked = || {
+            let unmasked = Rc::clone(&unmasked);
+            TraceEvent::CheckScreenshot(Box::new(move |ss: Screenshot| {
+                match unmasked.borrow_mut().replace(ss.clone()) {
+                    Some(unmasked) => ss != unmasked,
+                    None => true,
+                }
+            }))
+        };
+        let trace = vec![
+            check_unmasked(),
+            //the counter value changes between the captures; paint it out
+            TraceEvent::Mask(vec![Mask::Widget(Selector::text("0"))]),
+            check_masked(),
+            TraceEvent::Mask(vec![Mask::Widget(Selector::text("3"))]),
+            TraceEvent::Message(Message::IncrementPressed),
+            TraceEvent::Message(Message::IncrementPressed),
+            TraceEvent::Message(Message::IncrementPressed),
+            check_masked(),
+            //without the mask the changed value is visible
+            TraceEvent::Mask(Vec::new()),
+            check_unmasked(),
+        ];
+        let app_state: Counter = execute_iced_trace((), trace);
+        assert_eq!(app_state.value, 3)
+    }
+
+    #[test]
+    fn sync_is_stubbed() {
+        let trace = vec![
+            TraceEvent::Message(Message::IncrementPressed),
+            TraceEvent::Message(Message::SyncPressed),
+            TraceEvent::StateCheck(Box::new(|app: &Counter| app.value == 1)),
+            TraceEvent::Duration(Duration::from_millis(50)),
+            TraceEvent::StateCheck(Box::new(|app: &Counter| app.value == 1)),
+            TraceEvent::Duration(Duration::from_millis(50)),
+            TraceEvent::StateCheck(Box::new(|app: &Counter| app.value == 10)),
+        ];
+        let app_state: Counter = Runner::new()
+            .stub(
+                CommandStub::on(
+                    "slow sync",
+                    |message| matches!(message, Message::SyncPressed),
+                    || Message::Synced(10),
+                )
+                .delay(Duration::from_millis(100)),
+            )
+            .execute((), trace);
+        assert_eq!(app_state.value, 10)
+    }
+
+    #[test]
+    fn first_matching_stub_answers() {
+        let trace = vec![
+            TraceEvent::Message(Message::SyncPressed),
+            TraceEvent::StateCheck(Box::new(|app: &Counter| app.value == 5)),
+            TraceEvent::Duration(Duration::from_millis(100)),
+            TraceEvent::StateCheck(Box::new(|app: &Counter| app.value == 5)),
+        ];
+        let app_state: Counter = Runner::new()
+            .stub(CommandStub::on(
+                "sync",
+                |message| matches!(message, Message::SyncPressed),
+                || Message::Synced(5),
+            ))
+            .stub(
+                CommandStub::on(
+                    "slow sync",
+                    |message| matches!(message, Message::SyncPressed),
+                    || Message::Synced(10),
+                )
+                .delay(Duration::from_millis(100)),
+            )
+            .execute((), trace);
+        assert_eq!(app_state.value, 5)
+    }
+
+    #[test]
+    fn clipboard_is_virtual() {
+        let trace = vec![
+            TraceEvent::ClipboardCheck(Box::new(|contents| contents.is_none())),
+            TraceEvent::SetClipboard(String::from("42")),
+            TraceEvent::ClipboardCheck(Box::new(|contents| contents == Some("42"))),
+        ];
+        let _: Counter = execute_iced_trace((), trace);
+    }
+
+    #[test]
+    fn window_is_recorded() {
+        let trace = vec![
+            TraceEvent::Message(Message::IncrementPressed),
+            TraceEvent::WindowCheck(Box::new(|history: &WindowHistory| {
+                history.title() == Some("Counter - Iced")
+                    && history.mode() == Some(iced::window::Mode::Windowed)
+                    && history.size().is_none()
+            })),
+        ];
+        let _: Counter = execute_iced_trace((), trace);
+    }
+
+    #[test]
+    fn quitting_ends_the_trace() {
+        let trace = || {
+            vec![
+                TraceEvent::Message(Message::IncrementPressed),
+                TraceEvent::Message(Message::QuitPressed),
+                TraceEvent::Message(Message::IncrementPressed),
+                TraceEvent::AssertExited,
+            ]
+        };
+        let app_state: Counter = execute_iced_trace((), trace());
+        assert_eq!(app_state.value, 1);
+
+        let app_state: Counter = Runner::new()
+            .on_exit(ExitPolicy::Continue)
+            .execute((), trace());
+        assert_eq!(app_state.value, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "application exited with 1 trace events remaining")]
+    fn events_after_quitting_fail() {
+        let trace = vec![
+            TraceEvent::Message(Message::QuitPressed),
+            TraceEvent::Message(Message::IncrementPressed),
+            TraceEvent::AssertExited,
+        ];
+        let _: Counter = Runner::new()
+            .on_exit(ExitPolicy::FailIfEventsRemain)
+            .execute((), trace);
+    }
+
+    #[test]
+    fn sync_messages_are_logged() {
+        let trace = vec![
+            TraceEvent::Message(Message::IncrementPressed),
+            TraceEvent::Message(Message::SyncPressed),
+            TraceEvent::MessageCheck(Box::new(|log: &MessageLog<Message>| {
+                log.step_matches(
+                    1,
+                    &[
+                        &|message| matches!(message, Message::SyncPressed),
+                        &|message| matches!(message, Message::Synced(5)),
+                    ],
+                ) && log.at_step(1)[1].source == MessageSource::Stub(String::from("sync"))
+            })),
+        ];
+        let _: Counter = Runner::new()
+            .keep_messages()
+            .stub(CommandStub::on(
+                "sync",
+                |message| matches!(message, Message::SyncPressed),
+                || Message::Synced(5),
+            ))
+            .execute((), trace);
+    }
+
+    #[test]
+    fn sync_is_expected() {
+        let trace = vec![
+            TraceEvent::Message(Message::IncrementPressed),
+            TraceEvent::Message(Message::SyncPressed),
+            expect_message!(Message::IncrementPressed),
+            expect_message!(any Message::Synced(value) if *value > 0),
+        ];
+        let _: Counter = Runner::new()
+            .keep_messages()
+            .stub(CommandStub::on(
+                "sync",
+                |message| matches!(message, Message::SyncPressed),
+                || Message::Synced(5),
+            ))
+            .execute((), trace);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected the next message to match `Message::DecrementPressed`")]
+    fn unexpected_message_fails() {
+        let trace = vec![
+            TraceEvent::Message(Message::IncrementPressed),
+            expect_message!(Message::DecrementPressed),
+        ];
+        let _: Counter = Runner::new().keep_messages().execute((), trace);
+    }
+
+    #[test]
+    fn cursor_is_painted() {
+        //the default background; red and blue are swapped if frames are read as BGRA
+        const WHITE: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+        let trace = vec![
+            TraceEvent::MoveCursor(iced::Point::new(10.0, 10.0)),
+            TraceEvent::CheckScreenshot(Box::new(|ss: Screenshot| {
+                ss.pixel(10, 10) == CURSOR_COLOR
+                    && ss.pixel(4, 10) == CURSOR_COLOR
+                    && ss.pixel(10, 16) == CURSOR_COLOR
+                    //between the arms of the crosshair
+                    && ss.pixel(13, 13) == WHITE
+            })),
+            //the cursor leaves the corner to hover the button
+            TraceEvent::Hover(Selector::text("Increment").parent()),
+            TraceEvent::CheckScreenshot(Box::new(|ss: Screenshot| {
+                ss.pixel(10, 10) == WHITE && ss.pixel(4, 10) == WHITE
+            })),
+        ];
+        let _: Counter = Runner::new().show_cursor(true).execute((), trace);
+    }
 }
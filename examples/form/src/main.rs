@@ -0,0 +1,296 @@
+use iced::{
+    scrollable, text_input, Application, Checkbox, Color, Column, Command, Element, Length,
+    Scrollable, Settings, Text, TextInput,
+};
+
+/// Linen; its red and blue channels differ, so swapped channels show in screenshots
+const BACKGROUND: [u8; 3] = [0xfa, 0xf0, 0xe6];
+
+/// Background of the dark style
+const DARK_BACKGROUND: [u8; 3] = [0x20, 0x22, 0x28];
+
+pub fn main() -> iced::Result {
+    Form::run(Settings::default())
+}
+
+#[derive(Default)]
+struct Form {
+    name: String,
+    email: String,
+    accepted: bool,
+    dark: bool,
+    submitted: Option<(String, String)>,
+    name_input: text_input::State,
+    email_input: text_input::State,
+    terms: scrollable::State,
+}
+
+#[derive(Debug, Clone)]
+enum Message {
+    NameChanged(String),
+    EmailChanged(String),
+    TermsAccepted(bool),
+    Submitted,
+}
+
+impl Application for Form {
+    type Message = Message;
+    type Flags = ();
+    type Executor = iced::executor::Default;
+
+    fn new(_flags: ()) -> (Self, Command<Message>) {
+        (Self::default(), Command::none())
+    }
+
+    fn title(&self) -> String {
+        String::from("Form - Iced")
+    }
+
+    fn background_color(&self) -> Color {
+        let [r, g, b] = if self.dark {
+            DARK_BACKGROUND
+        } else {
+            BACKGROUND
+        };
+        Color::from_rgb8(r, g, b)
+    }
+
+    fn update(&mut self, message: Message) -> Command<Message> {
+        match message {
+            Message::NameChanged(name) => self.name = name,
+            Message::EmailChanged(email) => self.email = email,
+            Message::TermsAccepted(accepted) => self.accepted = accepted,
+            Message::Submitted => {
+                self.submitted = Some((self.name.clone(), self.email.clone()));
+            }
+        }
+        Command::none()
+    }
+
+    fn view(&mut self) -> Element<Message> {
+        let terms = (1..=30).fold(
+            Scrollable::new(&mut self.terms)
+                .height(Length::Units(200))
+                .spacing(10),
+            |terms, section| terms.push(Text::new(format!("Section {} of the terms", section))),
+        );
+        Column::new()
+            .padding(20)
+            .spacing(10)
+            .push(Text::new("Sign up"))
+            .push(
+                TextInput::new(
+                    &mut self.name_input,
+                    "Name",
+                    &self.name,
+                    Message::NameChanged,
+                )
+                .padding(10)
+                .on_submit(Message::Submitted),
+            )
+            .push(
+                TextInput::new(
+                    &mut self.email_input,
+                    "Email",
+                    &self.email,
+                    Message::EmailChanged,
+                )
+                .padding(10)
+                .on_submit(Message::Submitted),
+            )
+            .push(terms.push(Checkbox::new(
+                self.accepted,
+                "I accept the terms",
+                Message::TermsAccepted,
+            )))
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use iced::keyboard::KeyCode;
+    use iced_test::{
+        expect_message,
+        runners::{execute_iced_trace_variants, keys, Focusable, MatrixRun, Runner, StyleVariant},
+        LayoutSnapshot, Screenshot, Selector, TraceEvent,
+    };
+    use std::path::PathBuf;
+
+    /// The name input; its placeholder is only drawn while it is empty, so it is selected by its
+    /// place in the form
+    fn name_input() -> Selector {
+        Selector::Path(vec![1])
+    }
+
+    /// The email input
+    fn email_input() -> Selector {
+        Selector::Path(vec![2])
+    }
+
+    /// A runner that tabs between the name and email inputs
+    fn runner() -> Runner<Form> {
+        Runner::new()
+            .keep_messages()
+            .focusable(Focusable::new(email_input(), |form: &mut Form| {
+                &mut form.email_input
+            }))
+            .focusable(Focusable::new(name_input(), |form: &mut Form| {
+                &mut form.name_input
+            }))
+    }
+
+    #[test]
+    fn tab_moves_focus_in_order() {
+        let trace = vec![
+            TraceEvent::AssertFocusOrder(vec![name_input(), email_input()]),
+            TraceEvent::FocusCheck(Box::new(|focused| focused.is_none())),
+            TraceEvent::tab(),
+            TraceEvent::FocusCheck(Box::new(|focused| focused == Some(&name_input()))),
+            TraceEvent::tab(),
+            TraceEvent::FocusCheck(Box::new(|focused| focused == Some(&email_input()))),
+            TraceEvent::shift_tab(),
+            TraceEvent::FocusCheck(Box::new(|focused| focused == Some(&name_input()))),
+        ];
+        let _: Form = runner().execute((), trace);
+    }
+
+    #[test]
+    #[should_panic(expected = "are not registered as Focusables")]
+    fn unregistered_inputs_fail_focus_order() {
+        let trace = vec![TraceEvent::AssertFocusOrder(vec![name_input()])];
+        let _: Form = Runner::new()
+            .focusable(Focusable::new(name_input(), |form: &mut Form| {
+                &mut form.name_input
+            }))
+            .execute((), trace);
+    }
+
+    #[test]
+    fn tab_returns_to_filled_input() {
+        let trace = vec![
+            TraceEvent::type_text(name_input(), "Ada"),
+            TraceEvent::FocusCheck(Box::new(|focused| focused == Some(&name_input()))),
+            TraceEvent::tab(),
+            TraceEvent::FocusCheck(Box::new(|focused| focused == Some(&email_input()))),
+            TraceEvent::AssertFocusOrder(vec![name_input(), email_input()]),
+            TraceEvent::shift_tab(),
+            TraceEvent::FocusCheck(Box::new(|focused| focused == Some(&name_input()))),
+            TraceEvent::key(KeyCode::Enter),
+            expect_message!(any Message::Submitted),
+        ];
+        let form: Form = runner().execute((), trace);
+        assert_eq!(form.submitted, Some((String::from("Ada"), String::new())));
+    }
+
+    #[test]
+    fn enter_submits() {
+        let trace = vec![
+            TraceEvent::tab(),
+            TraceEvent::key(KeyCode::Enter),
+            expect_message!(Message::Submitted),
+        ];
+        let form: Form = runner().execute((), trace);
+        assert_eq!(form.submitted, Some((String::new(), String::new())));
+    }
+
+    #[test]
+    fn typing_edits_inputs() {
+        let trace = vec![
+            TraceEvent::type_text(Selector::text("Name"), "Ada"),
+            expect_message!(Message::NameChanged(name) if name == "A"),
+            expect_message!(Message::NameChanged(name) if name == "Ad"),
+            expect_message!(Message::NameChanged(name) if name == "Ada"),
+            TraceEvent::type_text(name_input(), format!("m{}", keys::BACKSPACE)),
+            TraceEvent::StateCheck(Box::new(|form: &Form| form.name == "Ada")),
+            TraceEvent::SetClipboard(String::from("ada@example.com")),
+            TraceEvent::type_text(
+                Selector::text("Email"),
+                format!("typo{}{}\n", keys::SELECT_ALL, keys::PASTE),
+            ),
+            expect_message!(any Message::EmailChanged(email) if email == "ada@example.com"),
+            expect_message!(Message::Submitted),
+        ];
+        let form: Form = runner().execute((), trace);
+        assert_eq!(
+            form.submitted,
+            Some((String::from("Ada"), String::from("ada@example.com")))
+        );
+    }
+
+    #[test]
+    fn terms_scroll_into_view() {
+        let in_view = |text: &'static str| {
+            TraceEvent::LayoutCheck(Box::new(move |layout: &LayoutSnapshot| {
+                layout.is_in_view(&Selector::text(text))
+            }))
+        };
+        let trace = vec![
+            in_view("Section 1 of the terms"),
+            TraceEvent::scroll_down(Selector::text("Section 1 of the terms"), 3.0),
+            TraceEvent::LayoutCheck(Box::new(|layout: &LayoutSnapshot| {
+                !layout.is_in_view(&Selector::text("Section 1 of the terms"))
+            })),
+            TraceEvent::scroll_into_view(Selector::text("I accept the terms")),
+            in_view("I accept the terms"),
+            TraceEvent::scroll_into_view(Selector::text("Section 1 of the terms")),
+            in_view("Section 1 of the terms"),
+        ];
+        let _: Form = runner().execute((), trace);
+    }
+
+    /// Returns true if the pixel at 1, 1 has the color `background`
+    fn has_background(ss: &Screenshot, background: [u8; 3]) -> bool {
+        let [r, g, b, a] = ss.pixel(1, 1);
+        //allow for rounding in the sRGB conversion
+        let close = |actual: u8, expected: u8| (i16::from(actual) - i16::from(expected)).abs() <= 1;
+        close(r, background[0]) && close(g, background[1]) && close(b, background[2]) && a == 0xff
+    }
+
+    #[test]
+    fn background_is_read_as_rgba() {
+        let trace = vec![TraceEvent::CheckScreenshot(Box::new(|ss: Screenshot| {
+            has_background(&ss, BACKGROUND)
+        }))];
+        let _: Form = runner().execute((), trace);
+    }
+
+    #[test]
+    fn variants_save_their_own_goldens() {
+        let golden = |label: &str| {
+            format!(
+                "{}/golden/variant_ss-{}.png",
+                env!("CARGO_MANIFEST_DIR"),
+                label
+            )
+        };
+        let variants = [
+            StyleVariant::new("light", |form: &mut Form| form.dark = false),
+            StyleVariant::new("dark", |form: &mut Form| form.dark = true),
+        ];
+        let report = execute_iced_trace_variants(
+            (),
+            || {
+                vec![
+                    //the variant is applied again before the screenshot, undoing this
+                    TraceEvent::SetState(Box::new(|form: &mut Form| form.dark = !form.dark)),
+                    TraceEvent::TakeScreenshot(PathBuf::from(format!(
+                        "{}/golden/variant_ss.png",
+                        env!("CARGO_MANIFEST_DIR")
+                    ))),
+                ]
+            },
+            &variants,
+        );
+
+        let labels: Vec<String> = report.runs.iter().map(MatrixRun::label).collect();
+        assert_eq!(labels, vec!["800x800@1x-light", "800x800@1x-dark"]);
+        let _ = report.unwrap_all();
+        let light = Screenshot::from_png(golden(&labels[0])).expect("light golden is missing");
+        let dark = Screenshot::from_png(golden(&labels[1])).expect("dark golden is missing");
+        assert!(has_background(&light, BACKGROUND));
+        assert!(has_background(&dark, DARK_BACKGROUND));
+        assert_ne!(light, dark);
+    }
+}
@@ -40,6 +40,7 @@
     clippy::module_name_repetitions
 )]
 
+pub mod query;
 pub mod rendering;
 pub mod runners;
 pub mod trace_events;
@@ -56,5 +57,9 @@ use iced_winit as runtime;
 #[cfg(all(not(target_arch = "wasm32"), feature = "glow"))]
 use iced_glutin as runtime;
 
-pub use rendering::screenshot::Screenshot;
-pub use trace_events::TraceEvent;
+pub use query::{LayoutSnapshot, Selector};
+pub use rendering::diff::Perceptual;
+pub use rendering::recording::Recording;
+pub use rendering::screenshot::{Screenshot, ScreenshotError};
+pub use rendering::{CompositorError, HeadlessConfig};
+pub use trace_events::{Mask, TraceEvent};
@@ -0,0 +1,577 @@
+//! Queries over the layout of an [`Application`]'s user interface.
+//!
+//! A [`LayoutSnapshot`] is captured from the layout nodes produced by `build_user_interface` and the
+//! primitives the renderer emits while drawing them. Widgets are located inside the snapshot with
+//! [`Selector`]s.
+//!
+//! [`Application`]: iced::Application
+use iced::{Point, Rectangle, Size, Vector};
+use iced_graphics::Primitive;
+use iced_native::{alignment, layout, Font, Layout};
+use std::fmt;
+
+/// Describes how to locate a widget in a [`LayoutSnapshot`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Selector {
+    /// The widget that draws exactly this text
+    Text(String),
+    /// The widget that draws text containing this substring
+    ContainsText(String),
+    /// The layout node reached by following child indices from the root node
+    Path(Vec<usize>),
+    /// The layout node that directly contains the node matched by the inner selector; e.g. the
+    /// button that wraps a text label
+    Parent(Box<Selector>),
+}
+
+impl Selector {
+    /// Creates a [`Selector::Text`]
+    pub fn text<S: Into<String>>(text: S) -> Self {
+        Self::Text(text.into())
+    }
+
+    /// Creates a [`Selector::ContainsText`]
+    pub fn contains_text<S: Into<String>>(text: S) -> Self {
+        Self::ContainsText(text.into())
+    }
+
+    /// Selects the parent of the node matched by this [`Selector`]
+    #[must_use]
+    pub fn parent(self) -> Self {
+        Self::Parent(Box::new(self))
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Text(text) => write!(f, "text `{}`", text),
+            Self::ContainsText(text) => write!(f, "text containing `{}`", text),
+            Self::Path(path) => {
+                let indices: Vec<String> = path.iter().map(ToString::to_string).collect();
+                write!(f, "path [{}]", indices.join(", "))
+            }
+            Self::Parent(inner) => write!(f, "parent of {}", inner),
+        }
+    }
+}
+
+/// A layout node with absolute bounds
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutNode {
+    /// Bounds of the node, relative to the window origin
+    pub bounds: Rectangle,
+    /// Child nodes
+    pub children: Vec<LayoutNode>,
+}
+
+impl LayoutNode {
+    /// Converts a [`layout::Node`] tree into absolute coordinates
+    #[must_use]
+    pub fn from_node(node: &layout::Node) -> Self {
+        Self::from_layout(Layout::new(node))
+    }
+
+    /// Converts the node of `layout` and its children, whose bounds are already absolute
+    fn from_layout(layout: Layout<'_>) -> Self {
+        Self {
+            bounds: layout.bounds(),
+            children: layout.children().map(Self::from_layout).collect(),
+        }
+    }
+}
+
+/// A piece of text drawn by the user interface
+#[derive(Debug, Clone, PartialEq)]
+pub struct DrawnText {
+    /// Text content
+    pub content: String,
+    /// Area covered by the rendered glyphs, relative to the window origin
+    pub bounds: Rectangle,
+    /// Area the text was laid out in, relative to the window origin
+    pub layout_bounds: Rectangle,
+    /// Innermost clip region the text was drawn inside of, if any; e.g. the viewport of a
+    /// scrollable
+    pub clip: Option<Rectangle>,
+    /// Offset the text was drawn at relative to its layout node, e.g. by scrolling
+    pub translation: Vector,
+}
+
+/// A node matched by a [`Selector`]
+struct Match {
+    /// Child indices leading from the root node to the node
+    path: Vec<usize>,
+    /// Offset the node is drawn at relative to its layout bounds
+    translation: Vector,
+    /// Clip region the node is drawn inside of, if any
+    clip: Option<Rectangle>,
+}
+
+/// Snapshot of the layout of a user interface for a single frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutSnapshot {
+    /// Bounds of the window the user interface was laid out in, in the logical units of the
+    /// layout: the physical window size divided by the window's and the application's scale
+    /// factors
+    window: Rectangle,
+    /// Root of the layout tree
+    root: LayoutNode,
+    /// Text drawn by the user interface, in draw order
+    texts: Vec<DrawnText>,
+}
+
+impl LayoutSnapshot {
+    /// Creates a new [`LayoutSnapshot`] from raw parts
+    #[must_use]
+    pub fn new(window: Size, root: LayoutNode, texts: Vec<DrawnText>) -> Self {
+        Self {
+            window: Rectangle::with_size(window),
+            root,
+            texts,
+        }
+    }
+
+    /// Creates a [`LayoutSnapshot`] from a layout tree and the primitives that were drawn for it.
+    ///
+    /// `window` is the logical size the tree was laid out in, e.g. `Viewport::logical_size`.
+    /// `measure` returns the size of a text's glyphs given its content, font size and font
+    pub fn from_primitives(
+        window: Size,
+        node: &layout::Node,
+        primitives: &[Primitive],
+        measure: impl Fn(&str, f32, Font) -> Size,
+    ) -> Self {
+        let mut texts = Vec::new();
+        for primitive in primitives {
+            collect_texts(primitive, Vector::new(0.0, 0.0), None, &measure, &mut texts);
+        }
+
+        Self::new(window, LayoutNode::from_node(node), texts)
+    }
+
+    /// Bounds of the window
+    #[must_use]
+    pub const fn window(&self) -> Rectangle {
+        self.window
+    }
+
+    /// Root of the layout tree
+    #[must_use]
+    pub const fn root(&self) -> &LayoutNode {
+        &self.root
+    }
+
+    /// All text drawn by the user interface
+    #[must_use]
+    pub fn texts(&self) -> &[DrawnText] {
+        &self.texts
+    }
+
+    /// Bounds of the first widget matched by `selector`
+    #[must_use]
+    pub fn bounds(&self, selector: &Selector) -> Option<Rectangle> {
+        self.all_bounds(selector).into_iter().next()
+    }
+
+    /// Bounds of every widget matched by `selector`
+    #[must_use]
+    pub fn all_bounds(&self, selector: &Selector) -> Vec<Rectangle> {
+        self.select(selector)
+            .into_iter()
+            .map(|found| self.node(&found.path).bounds + found.translation)
+            .collect()
+    }
+
+    /// Clip region the first widget matched by `selector` is drawn inside of, e.g. the viewport of
+    /// the scrollable it is in. `None` if it is not clipped or nothing matches
+    #[must_use]
+    pub fn clip(&self, selector: &Selector) -> Option<Rectangle> {
+        self.select(selector)
+            .into_iter()
+            .next()
+            .and_then(|found| found.clip)
+    }
+
+    /// Part of the first widget matched by `selector` that is drawn inside of the window and its
+    /// clip region
+    #[must_use]
+    pub fn visible_bounds(&self, selector: &Selector) -> Option<Rectangle> {
+        let bounds = self.bounds(selector)?.intersection(&self.window)?;
+        match self.clip(selector) {
+            Some(clip) => bounds.intersection(&clip),
+            None => Some(bounds),
+        }
+    }
+
+    /// Returns true if a widget matched by `selector` exists and lies entirely inside the window
+    /// and its clip region, e.g. it was scrolled into view
+    #[must_use]
+    pub fn is_in_view(&self, selector: &Selector) -> bool {
+        self.bounds(selector).map_or(false, |bounds| {
+            contains_rect(&self.window, &bounds)
+                && self
+                    .clip(selector)
+                    .map_or(true, |clip| contains_rect(&clip, &bounds))
+        })
+    }
+
+    /// Returns true if a widget matched by `selector` exists and lies entirely inside the window
+    #[must_use]
+    pub fn is_inside_window(&self, selector: &Selector) -> bool {
+        self.bounds(selector)
+            .map_or(false, |bounds| contains_rect(&self.window, &bounds))
+    }
+
+    /// Returns true if a widget matched by `selector` covers any part of the window
+    #[must_use]
+    pub fn is_visible(&self, selector: &Selector) -> bool {
+        self.bounds(selector)
+            .and_then(|bounds| self.window.intersection(&bounds))
+            .is_some()
+    }
+
+    /// Returns true if the widgets matched by `a` and `b` overlap. Widgets that only share an edge
+    /// do not overlap
+    #[must_use]
+    pub fn overlaps(&self, a: &Selector, b: &Selector) -> bool {
+        match (self.bounds(a), self.bounds(b)) {
+            (Some(a), Some(b)) => a.intersection(&b).is_some(),
+            _ => false,
+        }
+    }
+
+    /// Returns true if no two widgets matched by `selectors` overlap
+    #[must_use]
+    pub fn none_overlap(&self, selectors: &[Selector]) -> bool {
+        let bounds: Vec<Rectangle> = selectors
+            .iter()
+            .flat_map(|selector| self.all_bounds(selector))
+            .collect();
+
+        bounds.iter().enumerate().all(|(index, a)| {
+            bounds[index + 1..]
+                .iter()
+                .all(|b| a.intersection(b).is_none())
+        })
+    }
+
+    /// Returns true if text equal to `content` is drawn, and some of its glyphs fall outside of
+    /// its layout bounds, its clip region or the window
+    #[must_use]
+    pub fn is_text_clipped(&self, content: &str) -> bool {
+        self.texts
+            .iter()
+            .filter(|text| text.content == content)
+            .any(|text| {
+                !contains_rect(&text.layout_bounds, &text.bounds)
+                    || !contains_rect(&self.window, &text.bounds)
+                    || text
+                        .clip
+                        .map_or(false, |clip| !contains_rect(&clip, &text.bounds))
+            })
+    }
+
+    /// Every node matched by `selector`
+    fn select(&self, selector: &Selector) -> Vec<Match> {
+        match selector {
+            Selector::Text(text) => self.select_text(|content| content == text),
+            Selector::ContainsText(text) => {
+                self.select_text(|content| content.contains(text.as_str()))
+            }
+            Selector::Path(path) => {
+                let mut node = &self.root;
+                for index in path {
+                    match node.children.get(*index) {
+                        Some(child) => node = child,
+                        None => return Vec::new(),
+                    }
+                }
+                vec![Match {
+                    path: path.clone(),
+                    translation: Vector::new(0.0, 0.0),
+                    clip: None,
+                }]
+            }
+            Selector::Parent(inner) => {
+                let mut parents: Vec<Match> = Vec::new();
+                for mut found in self.select(inner) {
+                    if found.path.pop().is_none()
+                        || parents.iter().any(|parent| parent.path == found.path)
+                    {
+                        continue;
+                    }
+                    // a parent that covers the whole clip region is the scrollable itself or lies
+                    // outside of it, so it is not scrolled
+                    let bounds = self.node(&found.path).bounds;
+                    if found
+                        .clip
+                        .map_or(false, |clip| contains_rect(&bounds, &clip))
+                    {
+                        found.translation = Vector::new(0.0, 0.0);
+                        found.clip = None;
+                    }
+                    parents.push(found);
+                }
+                parents
+            }
+        }
+    }
+
+    /// The innermost nodes that contain text matching `predicate`
+    fn select_text(&self, predicate: impl Fn(&str) -> bool) -> Vec<Match> {
+        let mut found: Vec<Match> = Vec::new();
+        for text in self.texts.iter().filter(|text| predicate(&text.content)) {
+            let mut path = Vec::new();
+            let center = text.layout_bounds.center() - text.translation;
+            if innermost_containing(&self.root, center, &mut path)
+                && !found.iter().any(|other| other.path == path)
+            {
+                found.push(Match {
+                    path,
+                    translation: text.translation,
+                    clip: text.clip,
+                });
+            }
+        }
+        found
+    }
+
+    /// The node reached by following the child indices of `path` from the root node. Panics if
+    /// `path` does not lead to a node; paths come from [`LayoutSnapshot::select`]
+    fn node(&self, path: &[usize]) -> &LayoutNode {
+        path.iter()
+            .fold(&self.root, |node, index| &node.children[*index])
+    }
+}
+
+/// Finds the innermost node under `node` that contains `point`, recording the path taken
+fn innermost_containing(node: &LayoutNode, point: Point, path: &mut Vec<usize>) -> bool {
+    if !node.bounds.contains(point) {
+        return false;
+    }
+
+    for (index, child) in node.children.iter().enumerate() {
+        path.push(index);
+        if innermost_containing(child, point, path) {
+            return true;
+        }
+        let _ = path.pop();
+    }
+    true
+}
+
+/// Returns true if `inner` lies entirely inside of `outer`
+fn contains_rect(outer: &Rectangle, inner: &Rectangle) -> bool {
+    inner.x >= outer.x
+        && inner.y >= outer.y
+        && inner.x + inner.width <= outer.x + outer.width
+        && inner.y + inner.height <= outer.y + outer.height
+}
+
+/// Collects the text drawn by `primitive` and its children into `texts`. Text is positioned by
+/// the translations of enclosing primitives, summed up in `offset`, and clipped by `clip`, the
+/// innermost clip region so far. `measure` sizes the glyphs of each text
+fn collect_texts(
+    primitive: &Primitive,
+    offset: Vector,
+    clip: Option<Rectangle>,
+    measure: &impl Fn(&str, f32, Font) -> Size,
+    texts: &mut Vec<DrawnText>,
+) {
+    match primitive {
+        Primitive::Group { primitives } => {
+            for primitive in primitives {
+                collect_texts(primitive, offset, clip, measure, texts);
+            }
+        }
+        Primitive::Translate {
+            translation,
+            content,
+        } => collect_texts(content, offset + *translation, clip, measure, texts),
+        Primitive::Clip { bounds, content } => {
+            let bounds = *bounds + offset;
+            let clip = match clip {
+                Some(clip) => Some(clip.intersection(&bounds).unwrap_or(Rectangle {
+                    width: 0.0,
+                    height: 0.0,
+                    ..bounds
+                })),
+                None => Some(bounds),
+            };
+            collect_texts(content, offset, clip, measure, texts);
+        }
+        Primitive::Cached { cache } => collect_texts(cache, offset, clip, measure, texts),
+        Primitive::Text {
+            content,
+            bounds,
+            size,
+            font,
+            horizontal_alignment,
+            vertical_alignment,
+            ..
+        } => {
+            let anchor = *bounds + offset;
+            let glyphs = measure(content, *size, *font);
+
+            // positions a rectangle of `size` at the anchor, as the text's alignment places it
+            let align = |size: Size| {
+                let x = match horizontal_alignment {
+                    alignment::Horizontal::Left => anchor.x,
+                    alignment::Horizontal::Center => anchor.x - size.width / 2.0,
+                    alignment::Horizontal::Right => anchor.x - size.width,
+                };
+                let y = match vertical_alignment {
+                    alignment::Vertical::Top => anchor.y,
+                    alignment::Vertical::Center => anchor.y - size.height / 2.0,
+                    alignment::Vertical::Bottom => anchor.y - size.height,
+                };
+                Rectangle::new(Point::new(x, y), size)
+            };
+
+            texts.push(DrawnText {
+                content: content.clone(),
+                bounds: align(glyphs),
+                layout_bounds: align(anchor.size()),
+                clip,
+                translation: offset,
+            });
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A layout node with the given bounds and children
+    fn node(x: f32, y: f32, width: f32, height: f32, children: Vec<LayoutNode>) -> LayoutNode {
+        LayoutNode {
+            bounds: Rectangle {
+                x,
+                y,
+                width,
+                height,
+            },
+            children,
+        }
+    }
+
+    /// Unclipped, unscrolled text whose glyphs fill `bounds`
+    fn text(content: &str, bounds: Rectangle) -> DrawnText {
+        DrawnText {
+            content: content.to_owned(),
+            bounds,
+            layout_bounds: bounds,
+            clip: None,
+            translation: Vector::new(0.0, 0.0),
+        }
+    }
+
+    /// A column with two buttons, each wrapping a label
+    fn snapshot() -> LayoutSnapshot {
+        let increment_label = node(30.0, 30.0, 80.0, 20.0, vec![]);
+        let decrement_label = node(30.0, 130.0, 80.0, 20.0, vec![]);
+        let root = node(
+            0.0,
+            0.0,
+            200.0,
+            200.0,
+            vec![
+                node(20.0, 20.0, 100.0, 40.0, vec![increment_label.clone()]),
+                node(20.0, 120.0, 100.0, 40.0, vec![decrement_label.clone()]),
+            ],
+        );
+        LayoutSnapshot::new(
+            Size::new(200.0, 200.0),
+            root,
+            vec![
+                text("Increment", increment_label.bounds),
+                text("Decrement", decrement_label.bounds),
+            ],
+        )
+    }
+
+    #[test]
+    fn select_by_text_and_parent() {
+        let snapshot = snapshot();
+        assert_eq!(
+            snapshot.bounds(&Selector::text("Decrement")),
+            Some(Rectangle::new(
+                Point::new(30.0, 130.0),
+                Size::new(80.0, 20.0)
+            ))
+        );
+        assert_eq!(
+            snapshot.bounds(&Selector::text("Decrement").parent()),
+            snapshot.bounds(&Selector::Path(vec![1]))
+        );
+        assert_eq!(snapshot.bounds(&Selector::text("Missing")), None);
+        assert_eq!(
+            snapshot
+                .all_bounds(&Selector::contains_text("crement"))
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn selectors_display() {
+        assert_eq!(
+            Selector::text("Decrement").parent().to_string(),
+            "parent of text `Decrement`"
+        );
+        assert_eq!(
+            Selector::contains_text("crement").to_string(),
+            "text containing `crement`"
+        );
+        assert_eq!(Selector::Path(vec![1, 0]).to_string(), "path [1, 0]");
+    }
+
+    #[test]
+    fn window_and_overlap_checks() {
+        let snapshot = snapshot();
+        let buttons = [
+            Selector::text("Increment").parent(),
+            Selector::text("Decrement").parent(),
+        ];
+        assert!(snapshot.is_inside_window(&buttons[0]));
+        assert!(snapshot.none_overlap(&buttons));
+        assert!(snapshot.overlaps(&buttons[0], &Selector::text("Increment")));
+        assert!(!snapshot.is_text_clipped("Increment"));
+    }
+
+    #[test]
+    fn clipped_text() {
+        let mut snapshot = snapshot();
+        snapshot.texts[0].bounds.width = 120.0;
+        assert!(snapshot.is_text_clipped("Increment"));
+        assert!(!snapshot.is_text_clipped("Decrement"));
+    }
+
+    #[test]
+    fn scrolled_text() {
+        let mut snapshot = snapshot();
+        let viewport = Rectangle::new(Point::new(0.0, 0.0), Size::new(200.0, 100.0));
+        for text in &mut snapshot.texts {
+            text.clip = Some(viewport);
+            text.translation = Vector::new(0.0, -100.0);
+            text.layout_bounds = text.layout_bounds + text.translation;
+        }
+        let decrement = Selector::text("Decrement").parent();
+        assert_eq!(
+            snapshot.bounds(&decrement),
+            Some(Rectangle::new(
+                Point::new(20.0, 20.0),
+                Size::new(100.0, 40.0)
+            ))
+        );
+        assert!(snapshot.is_in_view(&decrement));
+        assert!(!snapshot.is_in_view(&Selector::text("Increment")));
+        assert_eq!(snapshot.visible_bounds(&Selector::text("Increment")), None);
+        assert_eq!(
+            snapshot.clip(&Selector::text("Decrement").parent().parent()),
+            None
+        );
+    }
+}
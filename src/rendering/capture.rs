@@ -1,85 +1,304 @@
 //! Utilities for rendering headless runners
 //
-use super::screenshot::Screenshot;
-use crate::runtime::{application::Application, settings::Window, Size};
+#[cfg(feature = "glow")]
+use super::headless_compositors::glow::Compositor as HeadlessCompositor;
+#[cfg(not(feature = "glow"))]
+use super::headless_compositors::wgpu::Compositor as HeadlessCompositor;
+use super::headless_compositors::{CompositorError, HeadlessConfig};
+use super::screenshot::{metadata, Screenshot};
+use crate::query::LayoutSnapshot;
+use crate::runners::WindowConfig;
+#[cfg(not(feature = "glow"))]
+use crate::runtime::settings::Window;
+use crate::runtime::{application::Application, Size};
 use iced_graphics::window::Compositor;
+use iced_graphics::Viewport;
+use iced_native::renderer::{self, Renderer as _};
+use iced_native::text::Renderer as _;
 use iced_native::user_interface::Cache;
+use iced_native::{layout, mouse, Layout};
+#[cfg(not(feature = "glow"))]
 use winit::platform::unix::EventLoopExtUnix;
 
 use std::mem::{drop, ManuallyDrop};
 
-use iced_winit::application::{build_user_interface, State};
-use iced_winit::{Debug, Mode};
+use iced_winit::application::build_user_interface;
+use iced_winit::Debug;
+#[cfg(not(feature = "glow"))]
+use iced_winit::Mode;
+
+/// Renderer used by headless runners
+type Renderer = iced_graphics::Renderer<crate::renderer::Backend>;
+
+/// Name of the graphics backend screenshots are rendered with
+#[cfg(not(feature = "glow"))]
+pub(crate) const BACKEND: &str = "wgpu";
+/// Name of the graphics backend screenshots are rendered with
+#[cfg(feature = "glow")]
+pub(crate) const BACKEND: &str = "glow";
 //
-/// Render one frame of an application with provided window size
-pub fn render_once<A>(application: &mut A, window_size: (u32, u32)) -> Screenshot
+/// Render one frame of an application in the provided window, with the adapter selected by
+/// `config`. Widgets under `cursor_position` are drawn hovered
+pub fn render_once<A>(
+    application: &mut A,
+    window: &WindowConfig,
+    config: &HeadlessConfig,
+    cursor_position: iced::Point,
+) -> Result<Screenshot, CompositorError>
 where
-    A: Application<Renderer = iced_graphics::Renderer<crate::renderer::Backend>> + 'static,
+    A: Application<Renderer = Renderer> + 'static,
 {
-    //#[cfg(feature = "glow")]
-    //let ss = render_glutin(application, window_size);
+    render_headless(application, window, config, cursor_position)
+}
+//
 
-    //#[cfg(not(feature = "glow"))]
+/// Lay out and draw one frame of an application in the provided window, capturing a
+/// [`LayoutSnapshot`] instead of rendering pixels. The snapshot holds the same layout its
+/// primitives were drawn with; overlays, such as open pick lists, are not drawn
+pub fn inspect_layout<A>(
+    application: &mut A,
+    window: &WindowConfig,
+    config: &HeadlessConfig,
+) -> Result<LayoutSnapshot, CompositorError>
+where
+    A: Application<Renderer = Renderer> + 'static,
+{
+    with_headless_window(application, window, config, |application, headless| {
+        let size = headless.viewport.logical_size();
+        let element = application.view();
+        let node = element.layout(&headless.renderer, &layout::Limits::new(Size::ZERO, size));
 
-    render_wgpu(application, window_size)
+        headless.renderer.clear();
+        element.draw(
+            &mut headless.renderer,
+            &renderer::Style::default(),
+            Layout::new(&node),
+            iced::Point::new(-1.0, -1.0),
+            &iced::Rectangle::with_size(size),
+        );
+        drop(element);
+
+        let primitives = headless
+            .renderer
+            .with_primitives(|_backend, primitives| primitives.to_vec());
+        let renderer = &headless.renderer;
+
+        LayoutSnapshot::from_primitives(size, &node, &primitives, |content, text_size, font| {
+            let (width, height) = renderer.measure(content, text_size as u16, font, Size::INFINITY);
+            Size::new(width, height)
+        })
+    })
 }
-//
 
-/// Winit implementation for rendering a single frame to a [`Screenshot`]
-fn render_wgpu<A>(application: &mut A, window_size: (u32, u32)) -> Screenshot
+/// Lay out an application in the provided window and locate its text inputs by the text cursor
+/// they show when hovered. Returns the center of every layout node that lies within a text input,
+/// so every input yields at least one point; inputs outside of the window are not located
+pub fn locate_text_inputs<A>(
+    application: &mut A,
+    window: &WindowConfig,
+    config: &HeadlessConfig,
+) -> Result<Vec<iced::Point>, CompositorError>
 where
-    A: Application<Renderer = iced_graphics::Renderer<crate::renderer::Backend>> + 'static,
+    A: Application<Renderer = Renderer> + 'static,
 {
-    use super::headless_compositors::wgpu::Compositor as HeadlessCompositor;
-    use winit::event_loop::EventLoop;
-    #[cfg(not(target_os = "ios"))]
-    let event_loop: EventLoop<A::Message> = EventLoop::new_any_thread();
-    #[cfg(target_os = "ios")]
-    let event_loop: EventLoop<A::Message> = EventLoop::with_user_event();
+    with_headless_window(application, window, config, |application, headless| {
+        let size = headless.viewport.logical_size();
+        let viewport = iced::Rectangle::with_size(size);
+        let element = application.view();
+        let node = element.layout(&headless.renderer, &layout::Limits::new(Size::ZERO, size));
+
+        let mut points = Vec::new();
+        let mut layouts = vec![Layout::new(&node)];
+        while let Some(layout) = layouts.pop() {
+            let center = layout.bounds().center();
+            if viewport.contains(center)
+                && element.mouse_interaction(layout, center, &viewport, &headless.renderer)
+                    == mouse::Interaction::Text
+            {
+                points.push(center);
+            }
+            layouts.extend(layout.children());
+        }
+
+        points
+    })
+}
 
+/// Lay out an application in the provided window and pass `events` to its widgets, with the
+/// cursor at `cursor_position`. Returns the messages the widgets produced
+pub fn dispatch_events<A>(
+    application: &mut A,
+    window: &WindowConfig,
+    config: &HeadlessConfig,
+    events: &[iced_native::Event],
+    cursor_position: iced::Point,
+    clipboard: &mut dyn iced_native::Clipboard,
+) -> Result<Vec<A::Message>, CompositorError>
+where
+    A: Application<Renderer = Renderer> + 'static,
+{
+    with_headless_window(application, window, config, |application, headless| {
+        let mut user_interface = ManuallyDrop::new(build_user_interface(
+            application,
+            Cache::default(),
+            &mut headless.renderer,
+            headless.viewport.logical_size(),
+            &mut headless.debug,
+        ));
+
+        let mut messages = Vec::new();
+        let _ = user_interface.update(
+            events,
+            cursor_position,
+            &headless.renderer,
+            clipboard,
+            &mut messages,
+        );
+        drop(ManuallyDrop::into_inner(user_interface));
+        messages
+    })
+}
+
+/// Renders a single frame to a [`Screenshot`] with the headless compositor of the enabled backend.
+/// The adapter it was rendered with is saved in its [`metadata`]
+fn render_headless<A>(
+    application: &mut A,
+    window: &WindowConfig,
+    config: &HeadlessConfig,
+    cursor_position: iced::Point,
+) -> Result<Screenshot, CompositorError>
+where
+    A: Application<Renderer = Renderer> + 'static,
+{
+    with_headless_window(application, window, config, |application, headless| {
+        let mut user_interface = ManuallyDrop::new(build_user_interface(
+            application,
+            Cache::default(),
+            &mut headless.renderer,
+            headless.viewport.logical_size(),
+            &mut headless.debug,
+        ));
+
+        let _ = user_interface.draw(&mut headless.renderer, cursor_position);
+        #[cfg(not(feature = "glow"))]
+        headless.window.request_redraw();
+
+        let ss = headless
+            .compositor
+            .render(
+                &mut headless.renderer,
+                &headless.viewport,
+                headless.background_color,
+                &headless.debug.overlay(),
+            )
+            .and_then(|_| headless.compositor.read())
+            .map(|ss| ss.with_metadata(metadata::ADAPTER, headless.compositor.adapter()));
+        drop(ManuallyDrop::into_inner(user_interface));
+        ss
+    })?
+}
+
+/// Everything needed to draw an application offscreen
+struct Headless {
+    /// Hidden window the compositor surface is created from; glow renders without a window
+    #[cfg(not(feature = "glow"))]
+    window: winit::window::Window,
+    /// Compositor that renders into an offscreen framebuffer
+    compositor: HeadlessCompositor,
+    /// Renderer that records primitives for the compositor
+    renderer: Renderer,
+    /// Surface of the hidden window
+    surface: <HeadlessCompositor as Compositor>::Surface,
+    /// Background color of the application
+    background_color: iced::Color,
+    /// Viewport the frame is rendered with; its physical size matches the framebuffer
+    viewport: Viewport,
+    /// Debug overlay passed to the compositor
+    debug: Debug,
+}
+
+/// Creates a headless compositor described by `window`, and runs `f` with it. With wgpu, a
+/// hidden window is created to select a compatible adapter.
+///
+/// The framebuffer has the physical size of the window: its logical size scaled by its scale
+/// factor. Like `iced_winit`, the viewport also applies the application's own scale factor, so
+/// widgets are laid out in the physical size divided by both factors. The compositor uses the
+/// window's antialiasing and the adapter selected by `config`
+// the glow compositor has no surface
+#[cfg_attr(feature = "glow", allow(clippy::let_unit_value))]
+fn with_headless_window<A, T>(
+    application: &mut A,
+    window: &WindowConfig,
+    config: &HeadlessConfig,
+    f: impl FnOnce(&mut A, &mut Headless) -> T,
+) -> Result<T, CompositorError>
+where
+    A: Application<Renderer = Renderer> + 'static,
+{
     let renderer_settings = crate::renderer::Settings {
+        antialiasing: window.antialiasing,
         ..crate::renderer::Settings::from_env()
     };
+    let window_size = window.size;
+    let scale_factor = window.scale_factor;
+
+    #[cfg(not(feature = "glow"))]
+    let (_event_loop, window) = hidden_window(&application.title(), window_size)?;
+    #[cfg(not(feature = "glow"))]
+    let compatible_window = Some(&window);
+    #[cfg(feature = "glow")]
+    let compatible_window: Option<&winit::window::Window> = None;
+
+    let (mut compositor, renderer) =
+        HeadlessCompositor::try_new(renderer_settings, config, compatible_window)?;
+    let viewport = Viewport::with_physical_size(
+        Size::new(
+            (f64::from(window_size.0) * scale_factor).round() as u32,
+            (f64::from(window_size.1) * scale_factor).round() as u32,
+        ),
+        scale_factor * application.scale_factor(),
+    );
+    let physical_size = viewport.physical_size();
+    #[cfg(not(feature = "glow"))]
+    let mut surface = compositor.create_surface(&window);
+    #[cfg(feature = "glow")]
+    let mut surface = ();
+    compositor.configure_surface(&mut surface, physical_size.width, physical_size.height);
+
+    let mut headless = Headless {
+        #[cfg(not(feature = "glow"))]
+        window,
+        compositor,
+        renderer,
+        surface,
+        background_color: application.background_color(),
+        viewport,
+        debug: Debug::new(),
+    };
+
+    Ok(f(application, &mut headless))
+}
+
+/// Creates a hidden window of the given logical size and the event loop it was built with; the
+/// window must be dropped before the event loop
+#[cfg(not(feature = "glow"))]
+fn hidden_window(
+    title: &str,
+    size: (u32, u32),
+) -> Result<(winit::event_loop::EventLoop<()>, winit::window::Window), CompositorError> {
+    use winit::event_loop::EventLoop;
+    #[cfg(not(target_os = "ios"))]
+    let event_loop: EventLoop<()> = EventLoop::new_any_thread();
+    #[cfg(target_os = "ios")]
+    let event_loop: EventLoop<()> = EventLoop::with_user_event();
 
     let window = Window {
-        size: window_size,
+        size,
         ..Window::default()
     }
-    .into_builder(&application.title(), Mode::Hidden, None, None)
+    .into_builder(title, Mode::Hidden, None, None)
     .build(&event_loop)
-    .expect("FAILURE IS NOT AN OPTION");
-    let (mut compositor, mut renderer) =
-        HeadlessCompositor::new(renderer_settings, Some(&window)).expect("FAILURE NOT ALLOWED!");
-    let state = State::new(application, &window);
-    let mut debug = Debug::new();
-    let mut surface = compositor.create_surface(&window);
-    compositor.configure_surface(&mut surface, window_size.0, window_size.1);
-
-    let mut user_interface = ManuallyDrop::new(build_user_interface(
-        application,
-        Cache::default(),
-        &mut renderer,
-        Size {
-            width: window_size.0 as f32,
-            height: window_size.1 as f32,
-        },
-        &mut debug,
-    ));
-
-    let _ = user_interface.draw(&mut renderer, iced::Point::default());
-    window.request_redraw();
-
-    compositor
-        .present(
-            &mut renderer,
-            &mut surface,
-            state.viewport(),
-            state.background_color(),
-            &debug.overlay(),
-        )
-        .expect("NO FAILURE");
-
-    let ss = compositor.read().expect("Compositor read failed");
-    drop(ManuallyDrop::into_inner(user_interface));
-    ss
+    .map_err(|error| CompositorError::Window(error.to_string()))?;
+
+    Ok((event_loop, window))
 }
@@ -0,0 +1,357 @@
+//! Perceptual comparison of [`Screenshot`]s
+//!
+//! Pixels are compared in the YIQ color space, which weighs differences the way they are perceived
+//! rather than per channel. Pixels that differ only because of anti-aliasing, e.g. after a driver
+//! rasterizes glyph edges slightly differently, are detected and not counted as differences. The
+//! metric follows the one used by [pixelmatch](https://github.com/mapbox/pixelmatch).
+use super::screenshot::Screenshot;
+
+/// Largest possible YIQ delta between two pixels
+const MAX_YIQ_DELTA: f64 = 35215.0;
+
+/// Settings for a perceptual comparison
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Perceptual {
+    /// Per pixel sensitivity, from 0 to 1. Smaller values flag smaller color differences
+    pub threshold: f64,
+    /// Fraction of pixels, from 0 to 1, that may differ before two screenshots are considered
+    /// different
+    pub max_diff_ratio: f64,
+    /// If set, pixels that differ due to anti-aliasing are not counted as differences
+    pub detect_antialiasing: bool,
+}
+
+impl Default for Perceptual {
+    fn default() -> Self {
+        Self {
+            threshold: 0.1,
+            max_diff_ratio: 0.0,
+            detect_antialiasing: true,
+        }
+    }
+}
+
+impl Perceptual {
+    /// Sets the per pixel sensitivity
+    #[must_use]
+    pub const fn threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+
+        self
+    }
+
+    /// Sets the fraction of pixels that may differ
+    #[must_use]
+    pub const fn max_diff_ratio(mut self, max_diff_ratio: f64) -> Self {
+        self.max_diff_ratio = max_diff_ratio;
+
+        self
+    }
+
+    /// Sets whether anti-aliased pixels are ignored
+    #[must_use]
+    pub const fn detect_antialiasing(mut self, detect_antialiasing: bool) -> Self {
+        self.detect_antialiasing = detect_antialiasing;
+
+        self
+    }
+}
+
+/// Outcome of a perceptual comparison
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffReport {
+    /// Pixels that differ perceptibly
+    pub differing_pixels: usize,
+    /// Pixels that differ, but were classified as anti-aliasing
+    pub antialiased_pixels: usize,
+    /// Pixels compared
+    pub total_pixels: usize,
+    /// Metadata keys describing the environments of the two screenshots that differ; see
+    /// [`Screenshot::metadata_mismatches`]
+    pub metadata_mismatches: Vec<&'static str>,
+}
+
+impl DiffReport {
+    /// Fraction of pixels that differ perceptibly, from 0 to 1
+    #[must_use]
+    pub fn score(&self) -> f64 {
+        if self.total_pixels == 0 {
+            0.0
+        } else {
+            self.differing_pixels as f64 / self.total_pixels as f64
+        }
+    }
+}
+
+/// Compares two screenshots perceptually. Returns `None` if their dimensions differ; see
+/// [`Screenshot::perceptual_diff`]
+pub(crate) fn compare(
+    screenshot: &Screenshot,
+    other: &Screenshot,
+    settings: &Perceptual,
+) -> Option<DiffReport> {
+    if screenshot.dimensions() != other.dimensions() {
+        return None;
+    }
+
+    let (width, height) = screenshot.dimensions();
+    let a = Image {
+        width,
+        height,
+        pixels: screenshot.rgba_pixels(),
+    };
+    let b = Image {
+        width,
+        height,
+        pixels: other.rgba_pixels(),
+    };
+
+    let max_delta = MAX_YIQ_DELTA * settings.threshold * settings.threshold;
+    let mut report = DiffReport {
+        differing_pixels: 0,
+        antialiased_pixels: 0,
+        total_pixels: width * height,
+        metadata_mismatches: screenshot.metadata_mismatches(other),
+    };
+
+    for y in 0..height {
+        for x in 0..width {
+            let delta = color_delta(a.pixel(x, y), b.pixel(x, y), false);
+            if delta.abs() <= max_delta {
+                continue;
+            }
+
+            if settings.detect_antialiasing
+                && (antialiased(&a, &b, x, y) || antialiased(&b, &a, x, y))
+            {
+                report.antialiased_pixels += 1;
+            } else {
+                report.differing_pixels += 1;
+            }
+        }
+    }
+
+    Some(report)
+}
+
+/// RGBA pixels of a screenshot
+struct Image {
+    /// Width in pixels
+    width: usize,
+    /// Height in pixels
+    height: usize,
+    /// Pixels in row-major order
+    pixels: Vec<[u8; 4]>,
+}
+
+impl Image {
+    /// The pixel at `(x, y)`
+    fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        self.pixels[y * self.width + x]
+    }
+
+    /// Returns true if at least three of the neighbours of the pixel at `(x, y)` are identical to
+    /// it; pixels on the edge of the image count as having one such neighbour
+    fn has_many_siblings(&self, x: usize, y: usize) -> bool {
+        let pixel = self.pixel(x, y);
+        let mut zeroes = usize::from(self.is_edge(x, y));
+
+        for (nx, ny) in self.neighbours(x, y) {
+            if self.pixel(nx, ny) == pixel {
+                zeroes += 1;
+                if zeroes > 2 {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Returns true if the pixel at `(x, y)` lies on the border of the image
+    fn is_edge(&self, x: usize, y: usize) -> bool {
+        x == 0 || y == 0 || x + 1 == self.width || y + 1 == self.height
+    }
+
+    /// Coordinates of the up to eight pixels surrounding `(x, y)`
+    fn neighbours(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> {
+        let xs = x.saturating_sub(1)..=(x + 1).min(self.width - 1);
+        let ys = y.saturating_sub(1)..=(y + 1).min(self.height - 1);
+
+        ys.flat_map(move |ny| xs.clone().map(move |nx| (nx, ny)))
+            .filter(move |neighbour| *neighbour != (x, y))
+    }
+}
+
+/// Returns true if the pixel at `(x, y)` of `image` is likely part of an anti-aliased edge, i.e.
+/// it lies between a darker and a brighter neighbour that are both part of solid regions in
+/// `image` and `other`
+fn antialiased(image: &Image, other: &Image, x: usize, y: usize) -> bool {
+    let pixel = image.pixel(x, y);
+    let mut zeroes = usize::from(image.is_edge(x, y));
+    let (mut min, mut max) = (0.0, 0.0);
+    let (mut min_at, mut max_at) = ((x, y), (x, y));
+
+    for (nx, ny) in image.neighbours(x, y) {
+        let delta = color_delta(pixel, image.pixel(nx, ny), true);
+        if delta == 0.0 {
+            zeroes += 1;
+            if zeroes > 2 {
+                return false;
+            }
+        } else if delta < min {
+            min = delta;
+            min_at = (nx, ny);
+        } else if delta > max {
+            max = delta;
+            max_at = (nx, ny);
+        }
+    }
+
+    if min == 0.0 || max == 0.0 {
+        return false;
+    }
+
+    (image.has_many_siblings(min_at.0, min_at.1) && other.has_many_siblings(min_at.0, min_at.1))
+        || (image.has_many_siblings(max_at.0, max_at.1)
+            && other.has_many_siblings(max_at.0, max_at.1))
+}
+
+/// Squared YIQ distance between two pixels, negative if `a` is brighter than `b`. If `luma_only`
+/// is set, only the signed difference in brightness is returned
+fn color_delta(a: [u8; 4], b: [u8; 4], luma_only: bool) -> f64 {
+    if a == b {
+        return 0.0;
+    }
+
+    let [r1, g1, b1] = blend_with_white(a);
+    let [r2, g2, b2] = blend_with_white(b);
+
+    let y = rgb_to_y(r1, g1, b1) - rgb_to_y(r2, g2, b2);
+    if luma_only {
+        return y;
+    }
+
+    let i = rgb_to_i(r1, g1, b1) - rgb_to_i(r2, g2, b2);
+    let q = rgb_to_q(r1, g1, b1) - rgb_to_q(r2, g2, b2);
+    let delta = 0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q;
+
+    if rgb_to_y(r1, g1, b1) > rgb_to_y(r2, g2, b2) {
+        -delta
+    } else {
+        delta
+    }
+}
+
+/// Composites a translucent pixel over white
+fn blend_with_white([r, g, b, a]: [u8; 4]) -> [f64; 3] {
+    let alpha = f64::from(a) / 255.0;
+    let blend = |channel: u8| 255.0 + (f64::from(channel) - 255.0) * alpha;
+
+    [blend(r), blend(g), blend(b)]
+}
+
+/// Brightness component of a color in the YIQ color space
+fn rgb_to_y(r: f64, g: f64, b: f64) -> f64 {
+    r * 0.298_895_31 + g * 0.586_622_47 + b * 0.114_482_23
+}
+
+/// Orange-blue chrominance component of a color in the YIQ color space
+fn rgb_to_i(r: f64, g: f64, b: f64) -> f64 {
+    r * 0.595_977_99 - g * 0.274_176_10 - b * 0.321_801_89
+}
+
+/// Purple-green chrominance component of a color in the YIQ color space
+fn rgb_to_q(r: f64, g: f64, b: f64) -> f64 {
+    r * 0.211_470_17 - g * 0.522_617_11 + b * 0.311_146_94
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rendering::screenshot::metadata;
+
+    /// A 64x64 screenshot with the pixels produced by `pixels`
+    fn screenshot(pixels: impl Fn(usize, usize) -> [u8; 4]) -> Screenshot {
+        let mut payload = Vec::with_capacity(4 * 64 * 64);
+        for y in 0..64 {
+            for x in 0..64 {
+                payload.extend_from_slice(&pixels(x, y));
+            }
+        }
+        Screenshot::new(payload, 64, 64)
+            .expect("payload matches dimensions")
+            .encode_png_frame()
+    }
+
+    #[test]
+    fn identical_and_faint_changes_match() {
+        let white = screenshot(|_, _| [0xff; 4]);
+        let faint = screenshot(|x, y| {
+            if (x, y) == (10, 10) {
+                [0xfc, 0xfc, 0xfc, 0xff]
+            } else {
+                [0xff; 4]
+            }
+        });
+
+        let settings = Perceptual::default();
+        assert_eq!(
+            white.perceptual_diff(&white, &settings).map(|r| r.score()),
+            Some(0.0)
+        );
+        assert!(white.perceptually_eq(&faint, &settings));
+    }
+
+    #[test]
+    fn real_changes_differ() {
+        let white = screenshot(|_, _| [0xff; 4]);
+        let square = screenshot(|x, y| {
+            if (20..30).contains(&x) && (20..30).contains(&y) {
+                [0x00, 0x00, 0x00, 0xff]
+            } else {
+                [0xff; 4]
+            }
+        });
+
+        let report = white
+            .perceptual_diff(&square, &Perceptual::default())
+            .expect("dimensions match");
+        assert_eq!(report.differing_pixels, 100);
+        assert!(!white.perceptually_eq(&square, &Perceptual::default()));
+        assert!(white.perceptually_eq(&square, &Perceptual::default().max_diff_ratio(0.05)));
+    }
+
+    #[test]
+    fn antialiased_edges_are_ignored() {
+        let edge = |shade: u8| {
+            screenshot(move |x, _| match x {
+                0..=31 => [0x00, 0x00, 0x00, 0xff],
+                32 => [shade, shade, shade, 0xff],
+                _ => [0xff; 4],
+            })
+        };
+
+        let report = edge(0x40)
+            .perceptual_diff(&edge(0xc0), &Perceptual::default())
+            .expect("dimensions match");
+        assert_eq!(report.differing_pixels, 0);
+        assert_eq!(report.antialiased_pixels, 64);
+    }
+
+    #[test]
+    fn metadata_mismatches_are_reported() {
+        let white = screenshot(|_, _| [0xff; 4]).with_metadata(metadata::WINDOW_SIZE, "64x64");
+        let golden = white.clone().with_metadata(metadata::WINDOW_SIZE, "32x32");
+
+        let report = white
+            .perceptual_diff(&golden, &Perceptual::default())
+            .expect("dimensions match");
+        assert_eq!(report.metadata_mismatches, vec![metadata::WINDOW_SIZE]);
+        assert!(white.perceptually_eq(&golden, &Perceptual::default()));
+        assert!(white
+            .perceptual_diff(&white, &Perceptual::default())
+            .expect("dimensions match")
+            .metadata_mismatches
+            .is_empty());
+    }
+}
@@ -0,0 +1,336 @@
+//! Image formats a [`Screenshot`] can be exported to and imported from
+//!
+//! PNG is always available. QOI, PPM and raw RGBA dumps are enabled by the `qoi`, `ppm` and `raw`
+//! cargo features. Other formats can be plugged in by implementing [`ImageFormat`]
+#[cfg(any(feature = "qoi", feature = "ppm", feature = "raw"))]
+use super::screenshot::{ByteSource, ColorType};
+use super::screenshot::{Screenshot, ScreenshotError};
+use std::io::{Read, Write};
+
+/// Encodes and decodes [`Screenshot`]s
+pub trait ImageFormat {
+    /// File extension used by the format, without a leading dot
+    fn extension(&self) -> &'static str;
+
+    /// Encodes `screenshot` and writes it to `buffer`
+    fn encode(
+        &self,
+        screenshot: &Screenshot,
+        buffer: &mut dyn Write,
+    ) -> Result<(), ScreenshotError>;
+
+    /// Decodes a screenshot read from `source`
+    fn decode(&self, source: &mut dyn Read) -> Result<Screenshot, ScreenshotError>;
+}
+
+/// Lossless PNG, see [`Screenshot::encode_png`] and [`Screenshot::decode_png`]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Png;
+
+impl ImageFormat for Png {
+    fn extension(&self) -> &'static str {
+        "png"
+    }
+
+    fn encode(
+        &self,
+        screenshot: &Screenshot,
+        buffer: &mut dyn Write,
+    ) -> Result<(), ScreenshotError> {
+        screenshot.encode_png(buffer)
+    }
+
+    fn decode(&self, source: &mut dyn Read) -> Result<Screenshot, ScreenshotError> {
+        Screenshot::decode_png(source)
+    }
+}
+
+/// Lossless [QOI](https://qoiformat.org), which encodes much faster than PNG
+#[cfg(feature = "qoi")]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Qoi;
+
+#[cfg(feature = "qoi")]
+impl ImageFormat for Qoi {
+    fn extension(&self) -> &'static str {
+        "qoi"
+    }
+
+    fn encode(
+        &self,
+        screenshot: &Screenshot,
+        buffer: &mut dyn Write,
+    ) -> Result<(), ScreenshotError> {
+        let encoded = qoi::encode_to_vec(
+            screenshot.as_rgba(),
+            screenshot.width() as u32,
+            screenshot.height() as u32,
+        )
+        .map_err(|error| ScreenshotError::Malformed(error.to_string()))?;
+        buffer.write_all(&encoded)?;
+
+        Ok(())
+    }
+
+    fn decode(&self, source: &mut dyn Read) -> Result<Screenshot, ScreenshotError> {
+        let mut data = Vec::new();
+        let _ = source.read_to_end(&mut data)?;
+        let (header, pixels) = qoi::decode_to_vec(&data)
+            .map_err(|error| ScreenshotError::Malformed(error.to_string()))?;
+        let color_type = match header.channels {
+            qoi::Channels::Rgb => ColorType::Rgb,
+            qoi::Channels::Rgba => ColorType::Rgba,
+        };
+
+        Screenshot::from_raw(
+            &pixels,
+            header.width as usize,
+            header.height as usize,
+            color_type,
+            &ByteSource::Png,
+        )
+    }
+}
+
+/// Binary [PPM](https://netpbm.sourceforge.net/doc/ppm.html) with 8 bits per channel
+///
+/// PPM has no alpha channel; it is dropped when encoding and decoded pixels are opaque
+#[cfg(feature = "ppm")]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Ppm;
+
+#[cfg(feature = "ppm")]
+impl ImageFormat for Ppm {
+    fn extension(&self) -> &'static str {
+        "ppm"
+    }
+
+    fn encode(
+        &self,
+        screenshot: &Screenshot,
+        buffer: &mut dyn Write,
+    ) -> Result<(), ScreenshotError> {
+        write!(
+            buffer,
+            "P6\n{} {}\n255\n",
+            screenshot.width(),
+            screenshot.height()
+        )?;
+        let rgb: Vec<u8> = screenshot
+            .as_rgba()
+            .chunks(4)
+            .flat_map(|pixel| pixel[..3].iter().copied())
+            .collect();
+        buffer.write_all(&rgb)?;
+
+        Ok(())
+    }
+
+    fn decode(&self, source: &mut dyn Read) -> Result<Screenshot, ScreenshotError> {
+        let mut data = Vec::new();
+        let _ = source.read_to_end(&mut data)?;
+
+        let mut header = PpmHeader {
+            data: &data,
+            position: 0,
+        };
+        if header.token() != Some(&b"P6"[..]) {
+            return Err(ScreenshotError::Malformed(String::from(
+                "not a binary ppm (P6)",
+            )));
+        }
+        let width = header.number()?;
+        let height = header.number()?;
+        if header.number()? != 255 {
+            return Err(ScreenshotError::Malformed(String::from(
+                "only ppm files with a maximum value of 255 are supported",
+            )));
+        }
+
+        // a single whitespace character separates the header from the pixels
+        let start = header.position + 1;
+        let end = width
+            .checked_mul(height)
+            .and_then(|pixels| pixels.checked_mul(3))
+            .and_then(|length| length.checked_add(start))
+            .ok_or_else(|| {
+                ScreenshotError::Malformed(format!(
+                    "ppm dimensions {}x{} are too large",
+                    width, height
+                ))
+            })?;
+        let pixels = data.get(start..end).ok_or_else(|| {
+            ScreenshotError::Malformed(String::from(
+                "ppm has fewer pixels than its header declares",
+            ))
+        })?;
+
+        Screenshot::from_raw(pixels, width, height, ColorType::Rgb, &ByteSource::Png)
+    }
+}
+
+/// Reads the whitespace separated tokens of a PPM header, skipping comments
+#[cfg(feature = "ppm")]
+struct PpmHeader<'a> {
+    /// Contents of the file
+    data: &'a [u8],
+    /// Offset of the first byte that was not read yet
+    position: usize,
+}
+
+#[cfg(feature = "ppm")]
+impl<'a> PpmHeader<'a> {
+    /// The next token, or `None` at the end of the data
+    fn token(&mut self) -> Option<&'a [u8]> {
+        loop {
+            match self.data.get(self.position)? {
+                byte if byte.is_ascii_whitespace() => self.position += 1,
+                b'#' => {
+                    while !matches!(self.data.get(self.position), None | Some(b'\n')) {
+                        self.position += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+
+        let start = self.position;
+        while matches!(self.data.get(self.position), Some(byte) if !byte.is_ascii_whitespace()) {
+            self.position += 1;
+        }
+
+        Some(&self.data[start..self.position])
+    }
+
+    /// The next token, parsed as a decimal number
+    fn number(&mut self) -> Result<usize, ScreenshotError> {
+        self.token()
+            .and_then(|token| std::str::from_utf8(token).ok())
+            .and_then(|token| token.parse().ok())
+            .ok_or_else(|| ScreenshotError::Malformed(String::from("invalid ppm header")))
+    }
+}
+
+/// Bare RGBA8 pixels in row-major order, without a header
+///
+/// Raw dumps do not store their dimensions, so decoding requires them to be set with
+/// [`Raw::dimensions`]
+#[cfg(feature = "raw")]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Raw {
+    /// Width and height of decoded screenshots
+    dimensions: Option<(usize, usize)>,
+}
+
+#[cfg(feature = "raw")]
+impl Raw {
+    /// Create a new [`Raw`] format that can only encode
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { dimensions: None }
+    }
+
+    /// Sets the width and height of decoded screenshots
+    #[must_use]
+    pub const fn dimensions(mut self, width: usize, height: usize) -> Self {
+        self.dimensions = Some((width, height));
+
+        self
+    }
+}
+
+#[cfg(feature = "raw")]
+impl ImageFormat for Raw {
+    fn extension(&self) -> &'static str {
+        "rgba"
+    }
+
+    fn encode(
+        &self,
+        screenshot: &Screenshot,
+        buffer: &mut dyn Write,
+    ) -> Result<(), ScreenshotError> {
+        buffer.write_all(screenshot.as_rgba())?;
+
+        Ok(())
+    }
+
+    fn decode(&self, source: &mut dyn Read) -> Result<Screenshot, ScreenshotError> {
+        let (width, height) = self.dimensions.ok_or_else(|| {
+            ScreenshotError::Malformed(String::from("dimensions of raw screenshot are unknown"))
+        })?;
+        let mut pixels = Vec::new();
+        let _ = source.read_to_end(&mut pixels)?;
+
+        Screenshot::from_raw(&pixels, width, height, ColorType::Rgba, &ByteSource::Png)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A screenshot with a gradient, so that every pixel differs
+    fn gradient() -> Screenshot {
+        let mut payload = Vec::with_capacity(4 * 64 * 64);
+        for y in 0..64_u8 {
+            for x in 0..64_u8 {
+                payload.extend_from_slice(&[x * 4, y * 4, x ^ y, 0xff]);
+            }
+        }
+        Screenshot::new(payload, 64, 64).expect("payload matches dimensions")
+    }
+
+    /// Encodes and decodes [`gradient`] with `format`
+    fn round_trip(format: &dyn ImageFormat) -> Screenshot {
+        let mut out_vec = vec![];
+        format
+            .encode(&gradient(), &mut out_vec)
+            .expect("Encoding failed");
+        format
+            .decode(&mut out_vec.as_slice())
+            .expect("Decoding failed")
+    }
+
+    #[test]
+    fn round_trip_png() {
+        assert_eq!(round_trip(&Png), gradient());
+    }
+
+    #[cfg(feature = "qoi")]
+    #[test]
+    fn round_trip_qoi() {
+        assert_eq!(round_trip(&Qoi), gradient());
+    }
+
+    #[cfg(feature = "ppm")]
+    #[test]
+    fn round_trip_ppm() {
+        assert_eq!(round_trip(&Ppm), gradient());
+
+        let commented = b"P6\n# made by hand\n2 1\n255\n\x10\x20\x30\x40\x50\x60";
+        let ss = Ppm.decode(&mut &commented[..]).expect("Decoding failed");
+        assert_eq!(ss.pixel(1, 0), [0x40, 0x50, 0x60, 0xff]);
+
+        let huge = b"P6\n99999999999 99999999999\n255\n\x10\x20\x30";
+        assert!(matches!(
+            Ppm.decode(&mut &huge[..]),
+            Err(ScreenshotError::Malformed(_))
+        ));
+    }
+
+    #[cfg(feature = "raw")]
+    #[test]
+    fn round_trip_raw() {
+        assert_eq!(round_trip(&Raw::new().dimensions(64, 64)), gradient());
+
+        let mut out_vec = vec![];
+        Raw::new()
+            .encode(&gradient(), &mut out_vec)
+            .expect("Encoding failed");
+        assert!(matches!(
+            Raw::new().decode(&mut out_vec.as_slice()),
+            Err(ScreenshotError::Malformed(_))
+        ));
+    }
+}
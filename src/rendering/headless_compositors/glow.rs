@@ -0,0 +1,365 @@
+//! Glow headless compositor
+//!
+//! Renders into an offscreen framebuffer of a headless OpenGL context. The context is created
+//! surfaceless through EGL where available, falling back to a pbuffer and finally to OSMesa
+use super::{CompositorError, HeadlessConfig};
+use crate::rendering::screenshot::{ByteSource, ColorType, Screenshot};
+use glow_api::HasContext;
+use glutin::dpi::PhysicalSize;
+use glutin::event_loop::EventLoop;
+use glutin::platform::unix::{EventLoopExtUnix, HeadlessContextExt};
+use glutin::{ContextBuilder, NotCurrent, PossiblyCurrent};
+use iced_glow::{Backend, Color, Error, Renderer, Settings, Viewport};
+use raw_window_handle::HasRawWindowHandle;
+
+/// A headless graphics backend for iced powered by `glow`.
+#[allow(missing_debug_implementations)]
+pub struct Compositor {
+    /// Current headless OpenGL context; it must stay alive while `gl` is used
+    _context: glutin::Context<PossiblyCurrent>,
+    /// OpenGL functions of the context
+    gl: glow_api::Context,
+    /// Offscreen framebuffer frames are rendered into, or why it could not be created
+    frame_buffer: Result<Framebuffer, CompositorError>,
+    /// Samples per pixel of the framebuffer, from the `antialiasing` setting
+    samples: i32,
+    /// `GL_RENDERER` of the context
+    adapter: String,
+    /// Event loop the context was created with; it must outlive the context
+    _event_loop: EventLoop<()>,
+}
+
+impl Compositor {
+    /// Creates a headless context, trying EGL surfaceless and pbuffer contexts before OSMesa
+    fn create_context(
+        event_loop: &EventLoop<()>,
+    ) -> Result<glutin::Context<NotCurrent>, glutin::CreationError> {
+        ContextBuilder::new()
+            .build_headless(event_loop, PhysicalSize::new(1, 1))
+            .or_else(|_| ContextBuilder::new().build_osmesa(PhysicalSize::new(1, 1)))
+    }
+
+    /// Creates a framebuffer with a single sRGB color attachment of `samples` samples
+    ///
+    /// # Safety
+    ///
+    /// The context of `gl` must be current
+    #[allow(unsafe_code)]
+    unsafe fn create_target(
+        gl: &glow_api::Context,
+        width: u32,
+        height: u32,
+        samples: i32,
+    ) -> Result<Target, CompositorError> {
+        let renderbuffer = gl
+            .create_renderbuffer()
+            .map_err(CompositorError::Framebuffer)?;
+        gl.bind_renderbuffer(glow_api::RENDERBUFFER, Some(renderbuffer));
+        if samples > 1 {
+            gl.renderbuffer_storage_multisample(
+                glow_api::RENDERBUFFER,
+                samples,
+                glow_api::SRGB8_ALPHA8,
+                width as i32,
+                height as i32,
+            );
+        } else {
+            gl.renderbuffer_storage(
+                glow_api::RENDERBUFFER,
+                glow_api::SRGB8_ALPHA8,
+                width as i32,
+                height as i32,
+            );
+        }
+
+        let framebuffer = match gl.create_framebuffer() {
+            Ok(framebuffer) => framebuffer,
+            Err(error) => {
+                gl.delete_renderbuffer(renderbuffer);
+                return Err(CompositorError::Framebuffer(error));
+            }
+        };
+        gl.bind_framebuffer(glow_api::FRAMEBUFFER, Some(framebuffer));
+        gl.framebuffer_renderbuffer(
+            glow_api::FRAMEBUFFER,
+            glow_api::COLOR_ATTACHMENT0,
+            glow_api::RENDERBUFFER,
+            Some(renderbuffer),
+        );
+
+        Ok(Target {
+            framebuffer,
+            renderbuffer,
+        })
+    }
+
+    /// Deletes a framebuffer object and its color attachment
+    ///
+    /// # Safety
+    ///
+    /// The context of `gl` must be current
+    #[allow(unsafe_code)]
+    unsafe fn delete_target(gl: &glow_api::Context, target: Target) {
+        gl.delete_framebuffer(target.framebuffer);
+        gl.delete_renderbuffer(target.renderbuffer);
+    }
+
+    /// Creates the framebuffer that images are rendered into
+    ///
+    /// `width` and `height` are physical dimensions; they must match the physical size of the
+    /// viewport that is presented. With antialiasing, frames are rendered into a multisampled
+    /// target that is resolved into a single sampled one before it is read
+    fn create_framebuffer(&self, width: u32, height: u32) -> Result<Framebuffer, CompositorError> {
+        let gl = &self.gl;
+
+        #[allow(unsafe_code)]
+        unsafe {
+            let resolve = if self.samples > 1 {
+                Some(Self::create_target(gl, width, height, 1)?)
+            } else {
+                None
+            };
+            let target = match Self::create_target(gl, width, height, self.samples) {
+                Ok(target) => target,
+                Err(error) => {
+                    if let Some(resolve) = resolve {
+                        Self::delete_target(gl, resolve);
+                    }
+                    return Err(error);
+                }
+            };
+
+            Ok(Framebuffer {
+                target,
+                resolve,
+                width,
+                height,
+            })
+        }
+    }
+
+    /// Frees the OpenGL objects of the framebuffer, if one was created
+    fn delete_framebuffer(&mut self) {
+        let frame_buffer = std::mem::replace(
+            &mut self.frame_buffer,
+            Err(CompositorError::FramebufferUninitialized),
+        );
+
+        if let Ok(frame_buffer) = frame_buffer {
+            for target in std::iter::once(frame_buffer.target).chain(frame_buffer.resolve) {
+                #[allow(unsafe_code)]
+                unsafe {
+                    Self::delete_target(&self.gl, target);
+                }
+            }
+        }
+    }
+
+    /// Reads the frame buffer into a screenshot
+    pub fn read(&self) -> Result<Screenshot, CompositorError> {
+        let frame_buffer = self.frame_buffer.as_ref().map_err(Clone::clone)?;
+        let (width, height) = (frame_buffer.width as usize, frame_buffer.height as usize);
+        let mut pixels = vec![0; width * height * 4];
+
+        #[allow(unsafe_code)]
+        unsafe {
+            let gl = &self.gl;
+            gl.bind_framebuffer(
+                glow_api::READ_FRAMEBUFFER,
+                Some(frame_buffer.target.framebuffer),
+            );
+
+            if let Some(resolve) = &frame_buffer.resolve {
+                gl.bind_framebuffer(glow_api::DRAW_FRAMEBUFFER, Some(resolve.framebuffer));
+                gl.blit_framebuffer(
+                    0,
+                    0,
+                    width as i32,
+                    height as i32,
+                    0,
+                    0,
+                    width as i32,
+                    height as i32,
+                    glow_api::COLOR_BUFFER_BIT,
+                    glow_api::NEAREST,
+                );
+                gl.bind_framebuffer(glow_api::READ_FRAMEBUFFER, Some(resolve.framebuffer));
+            }
+
+            gl.read_pixels(
+                0,
+                0,
+                width as i32,
+                height as i32,
+                glow_api::RGBA,
+                glow_api::UNSIGNED_BYTE,
+                glow_api::PixelPackData::Slice(&mut pixels),
+            );
+        }
+
+        // OpenGL returns rows from the bottom of the image to the top
+        let rows: Vec<u8> = pixels
+            .chunks((width * 4).max(1))
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
+
+        Screenshot::from_raw(&rows, width, height, ColorType::Rgba, &ByteSource::Png)
+            .map_err(|error| CompositorError::Readback(error.to_string()))
+    }
+
+    /// Describes the OpenGL implementation frames are rendered with, e.g. `llvmpipe (LLVM 12.0.0,
+    /// 256 bits)`
+    pub fn adapter(&self) -> String {
+        self.adapter.clone()
+    }
+
+    /// Creates a new [`Compositor`] and a [`Renderer`] for it; the fallible equivalent of
+    /// [`iced_graphics::window::Compositor::new`]
+    ///
+    /// OpenGL offers no adapter selection, so `_config` is ignored
+    pub fn try_new<W: HasRawWindowHandle>(
+        settings: Settings,
+        _config: &HeadlessConfig,
+        _compatible_window: Option<&W>,
+    ) -> Result<(Self, Renderer), CompositorError> {
+        let event_loop = EventLoop::new_any_thread();
+        let context = Self::create_context(&event_loop)
+            .map_err(|error| CompositorError::Context(error.to_string()))?;
+
+        #[allow(unsafe_code)]
+        let context = unsafe { context.make_current() }
+            .map_err(|(_, error)| CompositorError::Context(error.to_string()))?;
+
+        #[allow(unsafe_code)]
+        let gl = unsafe {
+            let gl = glow_api::Context::from_loader_function(|name| context.get_proc_address(name));
+
+            // same state the windowed glow compositor sets up
+            gl.enable(glow_api::MULTISAMPLE);
+            gl.enable(glow_api::BLEND);
+            gl.blend_func_separate(
+                glow_api::SRC_ALPHA,
+                glow_api::ONE_MINUS_SRC_ALPHA,
+                glow_api::ONE,
+                glow_api::ONE_MINUS_SRC_ALPHA,
+            );
+            gl.enable(glow_api::FRAMEBUFFER_SRGB);
+
+            gl
+        };
+
+        let samples = settings
+            .antialiasing
+            .map_or(1, |antialiasing| antialiasing.sample_count() as i32);
+        #[allow(unsafe_code)]
+        let adapter = unsafe { gl.get_parameter_string(glow_api::RENDERER) };
+        let renderer = Renderer::new(Backend::new(&gl, settings));
+
+        Ok((
+            Self {
+                _context: context,
+                gl,
+                frame_buffer: Err(CompositorError::FramebufferUninitialized),
+                samples,
+                adapter,
+                _event_loop: event_loop,
+            },
+            renderer,
+        ))
+    }
+
+    /// Renders the primitives of `renderer` into the frame buffer
+    pub fn render<T: AsRef<str>>(
+        &mut self,
+        renderer: &mut Renderer,
+        viewport: &Viewport,
+        background_color: Color,
+        overlay: &[T],
+    ) -> Result<(), CompositorError> {
+        let frame_buffer = self.frame_buffer.as_ref().map_err(Clone::clone)?;
+        let gl = &self.gl;
+        let size = viewport.physical_size();
+        let [r, g, b, a] = background_color.into_linear();
+
+        #[allow(unsafe_code)]
+        unsafe {
+            gl.bind_framebuffer(glow_api::FRAMEBUFFER, Some(frame_buffer.target.framebuffer));
+            gl.viewport(0, 0, size.width as i32, size.height as i32);
+            gl.clear_color(r, g, b, a);
+            gl.clear(glow_api::COLOR_BUFFER_BIT);
+        }
+
+        renderer.with_primitives(|backend, primitives| {
+            backend.present(gl, primitives, viewport, overlay);
+        });
+
+        #[allow(unsafe_code)]
+        unsafe {
+            gl.finish();
+        }
+
+        Ok(())
+    }
+}
+
+impl iced_graphics::window::Compositor for Compositor {
+    type Settings = Settings;
+    type Renderer = Renderer;
+    type Surface = ();
+
+    fn new<W: HasRawWindowHandle>(
+        settings: Self::Settings,
+        compatible_window: Option<&W>,
+    ) -> Result<(Self, Renderer), Error> {
+        Self::try_new(settings, &HeadlessConfig::from_env(), compatible_window)
+            .map_err(|error| Error::BackendError(error.to_string()))
+    }
+
+    fn create_surface<W: HasRawWindowHandle>(&mut self, _window: &W) -> Self::Surface {}
+
+    fn configure_surface(&mut self, _surface: &mut Self::Surface, width: u32, height: u32) {
+        self.delete_framebuffer();
+        self.frame_buffer = self.create_framebuffer(width, height);
+    }
+
+    fn present<T: AsRef<str>>(
+        &mut self,
+        renderer: &mut Self::Renderer,
+        _surface: &mut Self::Surface,
+        viewport: &Viewport,
+        background_color: Color,
+        overlay: &[T],
+    ) -> Result<(), iced_graphics::window::SurfaceError> {
+        self.render(renderer, viewport, background_color, overlay)
+            .map_err(|_| iced_graphics::window::SurfaceError::Lost)
+    }
+}
+
+impl Drop for Compositor {
+    fn drop(&mut self) {
+        // the context is still current; it is dropped after this
+        self.delete_framebuffer();
+    }
+}
+
+/// Offscreen framebuffer the [`Compositor`] renders to
+struct Framebuffer {
+    /// Target bound while presenting; multisampled if antialiasing is enabled
+    target: Target,
+    /// Single sampled target the multisampled `target` is resolved into before reading
+    resolve: Option<Target>,
+    /// Width in physical pixels
+    width: u32,
+    /// Height in physical pixels
+    height: u32,
+}
+
+/// A framebuffer object and its color attachment
+struct Target {
+    /// Framebuffer object
+    framebuffer: <glow_api::Context as HasContext>::Framebuffer,
+    /// sRGB color attachment of `framebuffer`
+    renderbuffer: <glow_api::Context as HasContext>::Renderbuffer,
+}
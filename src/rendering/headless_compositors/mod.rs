@@ -1,2 +1,138 @@
 //! Headless compositors
+#[cfg(feature = "glow")]
+pub mod glow;
+#[cfg(not(feature = "glow"))]
 pub mod wgpu;
+
+use std::error::Error;
+use std::fmt;
+
+/// Selects the graphics adapter headless rendering uses, so that goldens can be rendered with a
+/// known adapter, e.g. a software rasterizer.
+///
+/// Every field defaults from an environment variable; see [`HeadlessConfig::from_env`]. Only the
+/// wgpu backend selects adapters; the glow backend renders with whichever OpenGL implementation
+/// provides a headless context
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeadlessConfig {
+    /// Graphics APIs adapters are chosen from
+    pub backends: wgpu::Backends,
+    /// Power preference of the adapter. If unset, high performance adapters are preferred when
+    /// antialiasing is enabled
+    pub power_preference: Option<wgpu::PowerPreference>,
+    /// Only use a fallback adapter, i.e. a software rasterizer
+    pub force_fallback_adapter: bool,
+    /// Only use an adapter whose name contains this, ignoring case
+    pub adapter_name: Option<String>,
+}
+
+impl Default for HeadlessConfig {
+    fn default() -> Self {
+        Self::from_env()
+    }
+}
+
+impl HeadlessConfig {
+    /// Reads the configuration from the environment:
+    ///
+    /// - `WGPU_BACKEND`: comma separated backends, e.g. `vulkan,gl`; defaults to the primary
+    ///   backends
+    /// - `WGPU_POWER_PREF`: `low` or `high`
+    /// - `WGPU_FORCE_FALLBACK_ADAPTER`: `1` or `true`
+    /// - `WGPU_ADAPTER_NAME`: part of the adapter name, e.g. `llvmpipe`
+    #[must_use]
+    pub fn from_env() -> Self {
+        Self {
+            backends: wgpu::util::backend_bits_from_env().unwrap_or(wgpu::Backends::PRIMARY),
+            power_preference: wgpu::util::power_preference_from_env(),
+            force_fallback_adapter: std::env::var("WGPU_FORCE_FALLBACK_ADAPTER")
+                .map_or(false, |value| {
+                    value == "1" || value.eq_ignore_ascii_case("true")
+                }),
+            adapter_name: std::env::var("WGPU_ADAPTER_NAME").ok(),
+        }
+    }
+
+    /// Sets the graphics APIs adapters are chosen from
+    #[must_use]
+    pub const fn backends(mut self, backends: wgpu::Backends) -> Self {
+        self.backends = backends;
+
+        self
+    }
+
+    /// Sets the power preference of the adapter
+    #[must_use]
+    pub const fn power_preference(mut self, power_preference: wgpu::PowerPreference) -> Self {
+        self.power_preference = Some(power_preference);
+
+        self
+    }
+
+    /// Sets whether only a fallback adapter may be used
+    #[must_use]
+    pub const fn force_fallback_adapter(mut self, force_fallback_adapter: bool) -> Self {
+        self.force_fallback_adapter = force_fallback_adapter;
+
+        self
+    }
+
+    /// Only use an adapter whose name contains `name`, ignoring case
+    #[must_use]
+    pub fn adapter_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.adapter_name = Some(name.into());
+
+        self
+    }
+}
+
+/// Errors that can occur while setting up a headless compositor or rendering with it
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompositorError {
+    /// No graphics adapter compatible with the headless window was found, e.g. on a CI machine
+    /// without a GPU or software renderer
+    AdapterNotFound,
+    /// The surface of the headless window has no texture format supported by the adapter
+    NoSurfaceFormat,
+    /// The adapter refused to create a device
+    RequestDevice(String),
+    /// Creating the hidden window failed
+    Window(String),
+    /// Creating the graphics context failed
+    Context(String),
+    /// A frame was presented or read before the framebuffer was configured
+    FramebufferUninitialized,
+    /// Creating the offscreen framebuffer failed
+    Framebuffer(String),
+    /// Mapping the framebuffer for reading failed
+    Readback(String),
+}
+
+impl CompositorError {
+    /// Returns true if rendering is impossible on this machine, rather than failing for a
+    /// specific frame, e.g. because no display server is available for the hidden window
+    #[must_use]
+    pub const fn is_unavailable(&self) -> bool {
+        matches!(
+            self,
+            Self::AdapterNotFound | Self::NoSurfaceFormat | Self::Window(_) | Self::Context(_)
+        )
+    }
+}
+
+impl fmt::Display for CompositorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AdapterNotFound => write!(f, "no compatible graphics adapter found"),
+            Self::NoSurfaceFormat => write!(f, "surface has no format supported by the adapter"),
+            Self::RequestDevice(message) => write!(f, "requesting device failed: {}", message),
+            Self::Window(message) => write!(f, "creating hidden window failed: {}", message),
+            Self::Context(message) => write!(f, "creating graphics context failed: {}", message),
+            Self::FramebufferUninitialized => write!(f, "framebuffer was not configured"),
+            Self::Framebuffer(message) => write!(f, "creating framebuffer failed: {}", message),
+            Self::Readback(message) => write!(f, "reading framebuffer failed: {}", message),
+        }
+    }
+}
+
+impl Error for CompositorError {}
@@ -1,4 +1,5 @@
 //! WGPU headless compositor
+use super::{CompositorError, HeadlessConfig};
 use crate::rendering::screenshot::Screenshot;
 use iced_wgpu::{Backend, Color, Error, Renderer, Settings, Viewport};
 
@@ -17,6 +18,8 @@ pub struct Compositor {
     staging_belt: wgpu::util::StagingBelt,
     local_pool: futures::executor::LocalPool,
     format: wgpu::TextureFormat,
+    /// Adapter the device was requested from
+    adapter: wgpu::AdapterInfo,
     frame_buffer: Option<Framebuffer>,
     size: BufferDimensions,
 }
@@ -24,34 +27,59 @@ pub struct Compositor {
 impl Compositor {
     const CHUNK_SIZE: u64 = 10 * 1024;
 
-    /// Requests a new [`Compositor`] with the given [`Settings`].
+    /// Requests a new [`Compositor`] with the given [`Settings`], from an adapter selected by
+    /// `config`.
     ///
-    /// Returns `None` if no compatible graphics adapter could be found.
+    /// Fails if no compatible graphics adapter could be found, or it cannot provide a device
     pub async fn request<W: HasRawWindowHandle>(
         settings: Settings,
+        config: &HeadlessConfig,
         compatible_window: Option<&W>,
-    ) -> Option<Self> {
-        let instance = wgpu::Instance::new(settings.internal_backend);
+    ) -> Result<Self, CompositorError> {
+        let instance = wgpu::Instance::new(config.backends);
 
         #[allow(unsafe_code)]
         let compatible_surface =
             compatible_window.map(|window| unsafe { instance.create_surface(window) });
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: if settings.antialiasing.is_none() {
-                    wgpu::PowerPreference::LowPower
-                } else {
-                    wgpu::PowerPreference::HighPerformance
-                },
-                compatible_surface: compatible_surface.as_ref(),
-                force_fallback_adapter: false,
-            })
-            .await?;
+        let adapter = match &config.adapter_name {
+            Some(name) => {
+                let name = name.to_lowercase();
+                instance
+                    .enumerate_adapters(config.backends)
+                    .filter(|adapter| {
+                        compatible_surface
+                            .as_ref()
+                            .map_or(true, |surface| adapter.is_surface_supported(surface))
+                    })
+                    .filter(|adapter| {
+                        !config.force_fallback_adapter
+                            || adapter.get_info().device_type == wgpu::DeviceType::Cpu
+                    })
+                    .find(|adapter| adapter.get_info().name.to_lowercase().contains(&name))
+            }
+            None => {
+                instance
+                    .request_adapter(&wgpu::RequestAdapterOptions {
+                        power_preference: config.power_preference.unwrap_or(
+                            if settings.antialiasing.is_none() {
+                                wgpu::PowerPreference::LowPower
+                            } else {
+                                wgpu::PowerPreference::HighPerformance
+                            },
+                        ),
+                        compatible_surface: compatible_surface.as_ref(),
+                        force_fallback_adapter: config.force_fallback_adapter,
+                    })
+                    .await
+            }
+        }
+        .ok_or(CompositorError::AdapterNotFound)?;
 
         let format = compatible_surface
             .as_ref()
-            .and_then(|surface| surface.get_preferred_format(&adapter))?;
+            .and_then(|surface| surface.get_preferred_format(&adapter))
+            .ok_or(CompositorError::NoSurfaceFormat)?;
 
         #[cfg(target_arch = "wasm32")]
         let limits = wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits());
@@ -72,14 +100,14 @@ impl Compositor {
                 None,
             )
             .await
-            .ok()?;
+            .map_err(|error| CompositorError::RequestDevice(error.to_string()))?;
 
         let staging_belt = wgpu::util::StagingBelt::new(Self::CHUNK_SIZE);
         let local_pool = futures::executor::LocalPool::new();
 
         let frame_buffer = None;
 
-        Some(Self {
+        Ok(Self {
             instance,
             settings,
             device,
@@ -87,11 +115,33 @@ impl Compositor {
             staging_belt,
             local_pool,
             format,
+            adapter: adapter.get_info(),
             frame_buffer,
             size: BufferDimensions::default(),
         })
     }
 
+    /// Creates a new [`Compositor`] and a [`Renderer`] for it; the fallible equivalent of
+    /// [`iced_graphics::window::Compositor::new`]
+    pub fn try_new<W: HasRawWindowHandle>(
+        settings: Settings,
+        config: &HeadlessConfig,
+        compatible_window: Option<&W>,
+    ) -> Result<(Self, Renderer), CompositorError> {
+        let compositor = block_on(Self::request(settings, config, compatible_window))?;
+        let backend = compositor.create_backend();
+
+        Ok((compositor, Renderer::new(backend)))
+    }
+
+    /// Describes the adapter frames are rendered with, e.g. `llvmpipe (Vulkan, Cpu)`
+    pub fn adapter(&self) -> String {
+        format!(
+            "{} ({:?}, {:?})",
+            self.adapter.name, self.adapter.backend, self.adapter.device_type
+        )
+    }
+
     /// Creates a new rendering [`Backend`] for this [`Compositor`].
     pub fn create_backend(&self) -> Backend {
         Backend::new(&self.device, self.settings, self.format)
@@ -110,7 +160,8 @@ impl Compositor {
                 layout: wgpu::ImageDataLayout {
                     offset: 0,
                     bytes_per_row: Some(
-                        std::num::NonZeroU32::new(self.size.padded_bytes_per_row as u32).expect("Zero bytes per row"),
+                        std::num::NonZeroU32::new(self.size.padded_bytes_per_row as u32)
+                            .expect("Zero bytes per row"),
                     ),
                     rows_per_image: None,
                 },
@@ -123,15 +174,15 @@ impl Compositor {
         )
     }
     /// Interface for resizing the framebuffer that images are rendered into
+    ///
+    /// `width` and `height` are physical dimensions; they must match the physical size of the
+    /// viewport that is presented.
+    ///
+    /// The framebuffer is single sampled, like a swapchain image: the pipelines of
+    /// [`Backend`] are built for single sampled targets. With `settings.antialiasing` set, the
+    /// backend renders meshes into its own multisampled target and resolves it into this
+    /// framebuffer, exactly as it does on screen
     fn resize_framebuffer(&mut self, width: u32, height: u32) {
-        //FIXME: HACK! this is required because physical size is  1.5x logical size.
-        //If we don't do this, then the wgpu backend yells at us because we go out of bounds with
-        //certain wgpu commands 
-        //
-        //Longer term fix is to scale this based off of some aspect ratio provided by Window or
-        //viewport
-        let width = width * 3 / 2;
-        let height = height * 3 / 2;
         let framebuffer = {
             let size = BufferDimensions::new(width as usize, height as usize);
             let output = self.device.create_buffer(&wgpu::BufferDescriptor {
@@ -150,8 +201,7 @@ impl Compositor {
                 mip_level_count: 1,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
-                //format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                format: wgpu::TextureFormat::Bgra8UnormSrgb,
+                format: self.format,
                 usage: wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::RENDER_ATTACHMENT,
                 label: None,
             });
@@ -164,75 +214,49 @@ impl Compositor {
 }
 
 impl Compositor {
-    /// Reads the frame buffer into a screenshot
-    pub fn read(&self) -> Option<Screenshot> {
+    /// Reads the frame buffer into a screenshot, converting BGRA targets to RGBA
+    pub fn read(&self) -> Result<Screenshot, CompositorError> {
         let mut rv = Vec::new();
 
-        if let Some(frame) = &self.frame_buffer {
-            let buffer_slice = frame.output.slice(..);
-            let buffer_future = buffer_slice.map_async(wgpu::MapMode::Read);
-            self.device.poll(wgpu::Maintain::Wait);
-
-            if block_on(buffer_future) == Ok(()) {
-                rv.extend_from_slice(&buffer_slice.get_mapped_range());
+        let frame = self
+            .frame_buffer
+            .as_ref()
+            .ok_or(CompositorError::FramebufferUninitialized)?;
+        let buffer_slice = frame.output.slice(..);
+        let buffer_future = buffer_slice.map_async(wgpu::MapMode::Read);
+        self.device.poll(wgpu::Maintain::Wait);
+
+        block_on(buffer_future).map_err(|error| CompositorError::Readback(error.to_string()))?;
+        rv.extend_from_slice(&buffer_slice.get_mapped_range());
+        frame.output.unmap();
+        if matches!(
+            self.format,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+        ) {
+            // rows are padded to a multiple of 256 bytes, so every chunk is a whole pixel
+            for pixel in rv.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
             }
-
-            frame.output.unmap();
-
-            Some(Screenshot::new(rv, self.size.width, self.size.height).encode_png_frame())
-        } else {
-            None
         }
-    }
-}
 
-impl iced_graphics::window::Compositor for Compositor {
-    type Settings = Settings;
-    type Renderer = Renderer;
-    type Surface = wgpu::Surface;
-
-    fn new<W: HasRawWindowHandle>(
-        settings: Self::Settings,
-        compatible_window: Option<&W>,
-    ) -> Result<(Self, Renderer), Error> {
-        let compositor = futures::executor::block_on(Self::request(settings, compatible_window))
-            .ok_or(Error::AdapterNotFound)?;
-
-        let backend = compositor.create_backend();
-
-        Ok((compositor, Renderer::new(backend)))
+        Screenshot::new(rv, self.size.width, self.size.height)
+            .map_err(|error| CompositorError::Readback(error.to_string()))
     }
 
-    fn create_surface<W: HasRawWindowHandle>(&mut self, window: &W) -> Self::Surface {
-        #[allow(unsafe_code)]
-        let surface = unsafe { self.instance.create_surface(window) };
-        surface
-    }
-
-    fn configure_surface(&mut self, surface: &mut Self::Surface, width: u32, height: u32) {
-        surface.configure(
-            &self.device,
-            &wgpu::SurfaceConfiguration {
-                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
-                format: self.format,
-                present_mode: self.settings.present_mode,
-                width,
-                height,
-            },
-        );
-        //if self.settings.headless {
-        self.resize_framebuffer(width, height);
-        //}
-    }
-
-    fn present<T: AsRef<str>>(
+    /// Renders the primitives of `renderer` into the frame buffer and copies it to the readback
+    /// buffer
+    pub fn render<T: AsRef<str>>(
         &mut self,
-        renderer: &mut Self::Renderer,
-        _surface: &mut Self::Surface,
+        renderer: &mut Renderer,
         viewport: &Viewport,
         background_color: Color,
         overlay: &[T],
-    ) -> Result<(), iced_graphics::window::SurfaceError> {
+    ) -> Result<(), CompositorError> {
+        let frame_buffer = self
+            .frame_buffer
+            .as_ref()
+            .ok_or(CompositorError::FramebufferUninitialized)?;
+
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
@@ -240,13 +264,7 @@ impl iced_graphics::window::Compositor for Compositor {
             });
 
         let texture_viewer = wgpu::TextureViewDescriptor::default();
-        let owned_view = if let Some(ref frame_buffer) = self.frame_buffer {
-            frame_buffer.target.create_view(&texture_viewer)
-        } else {
-            panic!("Frame buffer unitialized! We die!");
-        };
-
-        let view = &owned_view;
+        let view = &frame_buffer.target.create_view(&texture_viewer);
 
         let _ = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("iced_wgpu::window::Compositor render pass"),
@@ -271,9 +289,6 @@ impl iced_graphics::window::Compositor for Compositor {
         });
 
         renderer.with_primitives(|backend, primitives| {
-            for primitive in primitives {
-                println!("prim is {:#?}", primitive);
-            }
             backend.present(
                 &mut self.device,
                 &mut self.staging_belt,
@@ -285,15 +300,7 @@ impl iced_graphics::window::Compositor for Compositor {
             );
         });
 
-        if let Some(ref frame_buffer) = self.frame_buffer {
-            if true {
-                self.copy_texture_to_buffer(
-                    &mut encoder,
-                    &frame_buffer.target,
-                    &frame_buffer.output,
-                );
-            }
-        }
+        self.copy_texture_to_buffer(&mut encoder, &frame_buffer.target, &frame_buffer.output);
 
         // Submit work
         self.staging_belt.finish();
@@ -311,6 +318,54 @@ impl iced_graphics::window::Compositor for Compositor {
     }
 }
 
+impl iced_graphics::window::Compositor for Compositor {
+    type Settings = Settings;
+    type Renderer = Renderer;
+    type Surface = wgpu::Surface;
+
+    fn new<W: HasRawWindowHandle>(
+        settings: Self::Settings,
+        compatible_window: Option<&W>,
+    ) -> Result<(Self, Renderer), Error> {
+        Self::try_new(settings, &HeadlessConfig::from_env(), compatible_window)
+            .map_err(|_| Error::AdapterNotFound)
+    }
+
+    fn create_surface<W: HasRawWindowHandle>(&mut self, window: &W) -> Self::Surface {
+        #[allow(unsafe_code)]
+        let surface = unsafe { self.instance.create_surface(window) };
+        surface
+    }
+
+    fn configure_surface(&mut self, surface: &mut Self::Surface, width: u32, height: u32) {
+        surface.configure(
+            &self.device,
+            &wgpu::SurfaceConfiguration {
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                format: self.format,
+                present_mode: self.settings.present_mode,
+                width,
+                height,
+            },
+        );
+        //if self.settings.headless {
+        self.resize_framebuffer(width, height);
+        //}
+    }
+
+    fn present<T: AsRef<str>>(
+        &mut self,
+        renderer: &mut Self::Renderer,
+        _surface: &mut Self::Surface,
+        viewport: &Viewport,
+        background_color: Color,
+        overlay: &[T],
+    ) -> Result<(), iced_graphics::window::SurfaceError> {
+        self.render(renderer, viewport, background_color, overlay)
+            .map_err(|_| iced_graphics::window::SurfaceError::Lost)
+    }
+}
+
 // TODO: This struct and Swapchain should be interchangeable, maybe an enum?
 /// Structure that the [`Compositor`] renders to.
 struct Framebuffer {
@@ -1,4 +1,10 @@
 //! Interfaces for rendering applications headlessly
 pub(crate) mod capture;
+pub mod diff;
+pub mod formats;
 mod headless_compositors;
+pub mod recording;
 pub mod screenshot;
+
+pub use headless_compositors::{CompositorError, HeadlessConfig};
+pub use wgpu::{Backends, PowerPreference};
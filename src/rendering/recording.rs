@@ -0,0 +1,244 @@
+//! Animated recordings of trace executions, encoded as APNG
+use super::screenshot::{create_file, Screenshot, ScreenshotError};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Controls when frames are captured during a recording
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameCapture {
+    /// Capture a frame after every step that can change application state
+    EveryStep,
+    /// Capture frames at a fixed rate of virtual time, which is advanced by
+    /// [`TraceEvent::Duration`]. Steps between two frames are not visible in the recording
+    ///
+    /// [`TraceEvent::Duration`]: crate::TraceEvent::Duration
+    FrameRate(u32),
+}
+
+/// Describes an animated recording of a trace
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recording {
+    /// Path the APNG is saved to
+    pub path: PathBuf,
+    /// When frames are captured
+    pub capture: FrameCapture,
+    /// How long a frame is shown if no [`TraceEvent::Duration`] follows it
+    ///
+    /// [`TraceEvent::Duration`]: crate::TraceEvent::Duration
+    pub step_delay: Duration,
+}
+
+impl Recording {
+    /// Records a frame after every step, saving the animation to `path`
+    pub fn every_step<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            capture: FrameCapture::EveryStep,
+            step_delay: Duration::from_millis(500),
+        }
+    }
+
+    /// Records `fps` frames per second of virtual time, saving the animation to `path`
+    pub fn frame_rate<P: Into<PathBuf>>(path: P, fps: u32) -> Self {
+        Self {
+            path: path.into(),
+            capture: FrameCapture::FrameRate(fps.max(1)),
+            step_delay: Duration::from_millis(500),
+        }
+    }
+
+    /// Sets how long a frame is shown if no [`TraceEvent::Duration`] follows it
+    ///
+    /// [`TraceEvent::Duration`]: crate::TraceEvent::Duration
+    #[must_use]
+    pub const fn step_delay(mut self, step_delay: Duration) -> Self {
+        self.step_delay = step_delay;
+
+        self
+    }
+}
+
+/// A captured frame and how long it is shown for
+struct Frame {
+    /// Contents of the frame
+    screenshot: Screenshot,
+    /// Virtual time that passed while the frame was current
+    delay: Duration,
+}
+
+/// Collects frames while a trace executes
+pub(crate) struct Recorder {
+    /// Recording configuration
+    config: Recording,
+    /// Frames captured so far
+    frames: Vec<Frame>,
+    /// Virtual time elapsed since the start of the trace
+    elapsed: Duration,
+    /// Virtual time at which the next frame is due, for [`FrameCapture::FrameRate`]
+    next_tick: Duration,
+}
+
+impl Recorder {
+    /// Creates a recorder without frames, at the start of virtual time
+    pub(crate) const fn new(config: Recording) -> Self {
+        Self {
+            config,
+            frames: Vec::new(),
+            elapsed: Duration::ZERO,
+            next_tick: Duration::ZERO,
+        }
+    }
+
+    /// Captures the initial state of the application
+    pub(crate) fn start(&mut self, render: impl FnOnce() -> Screenshot) {
+        self.push(render(), Duration::ZERO);
+        if let FrameCapture::FrameRate(fps) = self.config.capture {
+            self.next_tick = frame_interval(fps);
+        }
+    }
+
+    /// Called after a step that may have changed application state
+    pub(crate) fn step(&mut self, render: impl FnOnce() -> Screenshot) {
+        if self.config.capture == FrameCapture::EveryStep {
+            self.push(render(), Duration::ZERO);
+        }
+    }
+
+    /// Called when virtual time advances by `duration`
+    pub(crate) fn advance(&mut self, duration: Duration, mut render: impl FnMut() -> Screenshot) {
+        let end = self.elapsed + duration;
+        match self.config.capture {
+            FrameCapture::EveryStep => self.extend_last(duration),
+            FrameCapture::FrameRate(fps) => {
+                let interval = frame_interval(fps);
+                while self.next_tick <= end {
+                    self.extend_last(self.next_tick - self.elapsed);
+                    self.elapsed = self.next_tick;
+                    self.next_tick += interval;
+                    self.push(render(), Duration::ZERO);
+                }
+                self.extend_last(end - self.elapsed);
+            }
+        }
+        self.elapsed = end;
+    }
+
+    /// Encodes the captured frames and saves them to `path`, creating missing parent directories
+    pub(crate) fn save(&self, path: &Path) -> Result<(), ScreenshotError> {
+        self.encode(create_file(path)?)
+    }
+
+    /// Appends a frame; frames identical to the previous one extend it instead
+    fn push(&mut self, screenshot: Screenshot, delay: Duration) {
+        match self.frames.last_mut() {
+            Some(last) if last.screenshot == screenshot => last.delay += delay,
+            _ => self.frames.push(Frame { screenshot, delay }),
+        }
+    }
+
+    /// Shows the last frame for `duration` longer
+    fn extend_last(&mut self, duration: Duration) {
+        if let Some(last) = self.frames.last_mut() {
+            last.delay += duration;
+        }
+    }
+
+    /// Encodes the captured frames as an APNG and writes it to `buffer`
+    fn encode<W: Write>(&self, buffer: W) -> Result<(), ScreenshotError> {
+        let first = match self.frames.first() {
+            Some(frame) => &frame.screenshot,
+            None => return Ok(()),
+        };
+        let (width, height) = first.dimensions();
+
+        let mut encoder = png::Encoder::new(buffer, width as u32, height as u32);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_animated(self.frames.len() as u32, 0)?;
+
+        let mut writer = encoder.write_header()?;
+        for frame in &self.frames {
+            if frame.screenshot.dimensions() != (width, height) {
+                return Err(ScreenshotError::Malformed(String::from(
+                    "all frames of a recording must share dimensions",
+                )));
+            }
+
+            let delay = if frame.delay.is_zero() {
+                self.config.step_delay
+            } else {
+                frame.delay
+            };
+            writer.set_frame_delay(delay.as_millis().min(u128::from(u16::MAX)) as u16, 1000)?;
+
+            let data: Vec<u8> = frame.screenshot.rows().flatten().copied().collect();
+            writer.write_image_data(&data)?;
+        }
+        writer.finish()?;
+
+        Ok(())
+    }
+}
+
+/// Virtual time between two frames at `fps` frames per second
+fn frame_interval(fps: u32) -> Duration {
+    Duration::from_secs(1) / fps.max(1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A 64x64 frame filled with `value`
+    fn frame(value: u8) -> Screenshot {
+        Screenshot::new(vec![value; 4 * 64 * 64], 64, 64)
+            .expect("payload matches dimensions")
+            .encode_png_frame()
+    }
+
+    /// Delay of every frame captured by `recorder`
+    fn delays(recorder: &Recorder) -> Vec<Duration> {
+        recorder.frames.iter().map(|frame| frame.delay).collect()
+    }
+
+    #[test]
+    fn every_step_delays() {
+        let mut recorder = Recorder::new(Recording::every_step("unused.png"));
+        recorder.start(|| frame(0));
+        recorder.step(|| frame(1));
+        recorder.advance(Duration::from_millis(250), || frame(1));
+        recorder.step(|| frame(1));
+        recorder.step(|| frame(2));
+        assert_eq!(
+            delays(&recorder),
+            vec![Duration::ZERO, Duration::from_millis(250), Duration::ZERO]
+        );
+    }
+
+    #[test]
+    fn frame_rate_delays() {
+        let mut recorder = Recorder::new(Recording::frame_rate("unused.png", 10));
+        recorder.start(|| frame(0));
+        recorder.step(|| frame(1));
+        recorder.advance(Duration::from_millis(250), || frame(1));
+        assert_eq!(
+            delays(&recorder),
+            vec![Duration::from_millis(100), Duration::from_millis(150)]
+        );
+    }
+
+    #[test]
+    fn encode_apng() {
+        let mut recorder = Recorder::new(Recording::every_step("unused.png"));
+        recorder.start(|| frame(0));
+        recorder.step(|| frame(1));
+
+        let mut out_vec = vec![];
+        recorder.encode(&mut out_vec).expect("Encoding failed");
+        let decoder = png::Decoder::new(out_vec.as_slice());
+        let reader = decoder.read_info().expect("Decoder failed reading info");
+        let animation = reader.info().animation_control.expect("Not animated");
+        assert_eq!(animation.num_frames, 2);
+    }
+}
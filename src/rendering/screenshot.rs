@@ -1,39 +1,106 @@
 //! Data structure representing a screenshot
+use super::diff::{self, DiffReport, Perceptual};
+use super::formats::ImageFormat;
+use iced::Rectangle;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
 use std::error::Error;
+use std::fmt;
 use std::fs::File;
-use std::io::Write;
+use std::hash::{Hash, Hasher};
+use std::io::{Read, Write};
+use std::path::Path;
 use std::sync::Arc;
-#[derive(Debug, Clone, PartialEq)]
+
+/// Color that masked regions of a [`Screenshot`] are painted with, as RGBA
+pub const MASK_COLOR: [u8; 4] = [0xff, 0x00, 0xff, 0xff];
+
+/// Color that cursor markers are painted with, as RGBA
+pub const CURSOR_COLOR: [u8; 4] = [0xff, 0x00, 0x00, 0xff];
+
+/// Length of each arm of a cursor marker's crosshair, in pixels
+const CURSOR_RADIUS: u32 = 6;
+
+/// Number of bytes that encode a single pixel of a [`Screenshot`]
+const BYTES_PER_PIXEL: usize = 4;
+
+/// Well-known keys of [`Screenshot::metadata`], set by the runners when a screenshot is taken
+pub mod metadata {
+    /// Name of the trace, see [`Runner::name`](crate::runners::Runner::name)
+    pub const TRACE: &str = "Trace";
+    /// Index of the [`TraceEvent`](crate::TraceEvent) that took the screenshot
+    pub const STEP: &str = "Step";
+    /// Name of the [`StyleVariant`](crate::runners::StyleVariant), if any
+    pub const VARIANT: &str = "Variant";
+    /// Logical window size, e.g. `800x600`
+    pub const WINDOW_SIZE: &str = "Window size";
+    /// Scale factor of the window
+    pub const SCALE_FACTOR: &str = "Scale factor";
+    /// Graphics backend that rendered the screenshot
+    pub const BACKEND: &str = "Backend";
+    /// Graphics adapter that rendered the screenshot, see
+    /// [`HeadlessConfig`](crate::rendering::HeadlessConfig)
+    pub const ADAPTER: &str = "Adapter";
+
+    /// Keys describing the environment a screenshot was rendered in. Screenshots that differ in
+    /// any of these are unlikely to match, see [`Screenshot::metadata_mismatch`]
+    pub const ENVIRONMENT: [&str; 4] = [WINDOW_SIZE, SCALE_FACTOR, BACKEND, ADAPTER];
+}
+
+#[derive(Debug, Clone)]
 /// A single screencap.
+///
+/// Pixels are stored as unpadded RGBA8 regardless of where the screenshot came from, so equality
+/// and hashing only compare image contents; metadata is ignored
 pub struct Screenshot {
-    /// Raw bytes that represent the screenshot; encoded as png
+    /// Raw bytes that represent the screenshot; unpadded rows of RGBA8 pixels
     payload: Arc<Vec<u8>>,
     /// Width of the image in pixels
     width: usize,
     /// Height of the image in pixels
     height: usize,
-    /// Encodes color format of the png
-    color_encoding: ColorType,
-    /// Encodes origin of the payload bytes
-    source_encoding: ByteSource,
+    /// Describes how the screenshot was produced; saved to png tEXt chunks
+    metadata: BTreeMap<String, String>,
+}
+
+impl PartialEq for Screenshot {
+    fn eq(&self, other: &Self) -> bool {
+        self.width == other.width && self.height == other.height && self.payload == other.payload
+    }
+}
+
+impl Eq for Screenshot {}
+
+impl Hash for Screenshot {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.payload.hash(state);
+        self.width.hash(state);
+        self.height.hash(state);
+    }
 }
 #[derive(Debug, Clone, Copy, PartialEq)]
-///Decribes pixel encoding. Equivalent to [`png::ColorType`], maybe should be removed
+///Decribes pixel encoding of raw bytes passed to [`Screenshot::from_raw`]. Equivalent to
+///[`png::ColorType`], maybe should be removed
 pub enum ColorType {
-    /// Screenshot has RBGA format
+    /// Bytes have RBGA format
     Rgba,
-    /// Screenshot has RBG format
+    /// Bytes have RBG format
     Rgb,
+    /// Bytes have a single gray channel
+    Grayscale,
+    /// Bytes have a gray channel followed by an alpha channel
+    GrayscaleAlpha,
 }
 
-///Describes the source of the payload bytes
+///Describes the source of raw bytes passed to [`Screenshot::from_raw`]
 ///
-///This controls the alignment of the payload bytes in the [`Screenshot`]
+///This controls the alignment of rows of pixels
 #[derive(Debug, Clone, PartialEq)]
 pub enum ByteSource {
-    /// payload bytes come from a headless WGPU compositor
+    /// payload bytes come from a headless WGPU compositor; rows are padded to
+    /// [`wgpu::COPY_BYTES_PER_ROW_ALIGNMENT`]
     WGPU,
-    /// payload bytes come from a png file or encoded Screenshot object
+    /// payload bytes come from a png file or encoded Screenshot object; rows are not padded
     Png,
 }
 
@@ -42,125 +109,583 @@ impl Into<png::ColorType> for ColorType {
         match self {
             Self::Rgb => png::ColorType::Rgb,
             Self::Rgba => png::ColorType::Rgba,
+            Self::Grayscale => png::ColorType::Grayscale,
+            Self::GrayscaleAlpha => png::ColorType::GrayscaleAlpha,
         }
     }
 }
 
-impl From<png::ColorType> for ColorType {
-    fn from(color: png::ColorType) -> Self {
+impl TryFrom<png::ColorType> for ColorType {
+    type Error = ScreenshotError;
+
+    fn try_from(color: png::ColorType) -> Result<Self, Self::Error> {
         match color {
-            png::ColorType::Rgb => Self::Rgb,
-            png::ColorType::Rgba => Self::Rgba,
-            _ => panic!("Unsupported color"),
+            png::ColorType::Rgb => Ok(Self::Rgb),
+            png::ColorType::Rgba => Ok(Self::Rgba),
+            png::ColorType::Grayscale => Ok(Self::Grayscale),
+            png::ColorType::GrayscaleAlpha => Ok(Self::GrayscaleAlpha),
+            png::ColorType::Indexed => Err(ScreenshotError::UnsupportedColor(color)),
         }
     }
 }
 
-impl Screenshot {
-    /// Create a new [`Screenshot`] object
-    #[must_use]
-    pub fn new(payload: Vec<u8>, width: usize, height: usize) -> Self {
-        Self {
-            payload: Arc::new(payload),
-            width,
-            height,
-            color_encoding: ColorType::Rgba,
-            source_encoding: ByteSource::WGPU,
+/// Errors that can occur while reading or writing a [`Screenshot`]
+#[derive(Debug)]
+pub enum ScreenshotError {
+    /// Opening, creating or writing a file failed
+    Io(std::io::Error),
+    /// Encoding a png failed
+    Encoding(png::EncodingError),
+    /// Decoding a png failed, e.g. because it is malformed
+    Decoding(png::DecodingError),
+    /// Pixels of this color type cannot be converted to RGBA
+    UnsupportedColor(png::ColorType),
+    /// Data could not be encoded or decoded by an [`ImageFormat`]
+    Malformed(String),
+    /// Raw pixel data does not match the dimensions and color type it was declared with
+    Length {
+        /// Number of bytes the dimensions and color type require
+        expected: usize,
+        /// Number of bytes that were passed
+        actual: usize,
+    },
+}
+
+impl fmt::Display for ScreenshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "screenshot IO failed: {}", error),
+            Self::Encoding(error) => write!(f, "encoding png failed: {}", error),
+            Self::Decoding(error) => write!(f, "decoding png failed: {}", error),
+            Self::UnsupportedColor(color) => {
+                let name = match color {
+                    png::ColorType::Grayscale => "grayscale",
+                    png::ColorType::Rgb => "RGB",
+                    png::ColorType::Indexed => "indexed",
+                    png::ColorType::GrayscaleAlpha => "grayscale with alpha",
+                    png::ColorType::Rgba => "RGBA",
+                };
+                write!(f, "unsupported png color type {}", name)
+            }
+            Self::Malformed(message) => write!(f, "malformed image: {}", message),
+            Self::Length { expected, actual } => write!(
+                f,
+                "expected {} bytes of pixel data, found {}",
+                expected, actual
+            ),
         }
     }
+}
 
-    /// Sets the encoding field for a [`Screenshot`] object
-    #[must_use]
-    pub fn color_encoding(mut self, color_type: ColorType) -> Self {
-        self.color_encoding = color_type;
+impl Error for ScreenshotError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Encoding(error) => Some(error),
+            Self::Decoding(error) => Some(error),
+            Self::UnsupportedColor(_) | Self::Malformed(_) | Self::Length { .. } => None,
+        }
+    }
+}
 
-        self
+impl From<std::io::Error> for ScreenshotError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<png::EncodingError> for ScreenshotError {
+    fn from(error: png::EncodingError) -> Self {
+        Self::Encoding(error)
+    }
+}
+
+impl From<png::DecodingError> for ScreenshotError {
+    fn from(error: png::DecodingError) -> Self {
+        Self::Decoding(error)
+    }
+}
+
+/// Creates the file at `path` for writing, creating missing parent directories first
+pub(crate) fn create_file(path: &Path) -> std::io::Result<File> {
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
+
+    File::create(path)
+}
+
+impl ColorType {
+    /// Number of bytes that encode a single pixel
+    const fn bytes_per_pixel(self) -> usize {
+        match self {
+            Self::Rgba => std::mem::size_of::<u32>(),
+            Self::Rgb => std::mem::size_of::<u8>() * 3,
+            Self::Grayscale => std::mem::size_of::<u8>(),
+            Self::GrayscaleAlpha => std::mem::size_of::<u8>() * 2,
+        }
+    }
+}
+
+impl ByteSource {
+    /// Number of bytes between the starts of two rows holding `unpadded_bytes_per_row` bytes of
+    /// pixels each
+    fn padded_bytes_per_row(&self, unpadded_bytes_per_row: usize) -> usize {
+        let align = match self {
+            Self::WGPU => wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize,
+            Self::Png => 0,
+        };
+
+        let padded_bytes_per_row_padding = if align != 0 {
+            (align - unpadded_bytes_per_row % align) % align
+        } else {
+            0
+        };
+
+        unpadded_bytes_per_row + padded_bytes_per_row_padding
+    }
+}
+
+impl Screenshot {
+    /// Create a new [`Screenshot`] object from RGBA bytes read back from a headless WGPU
+    /// compositor
+    ///
+    /// Fails with [`ScreenshotError::Length`] if `payload` does not hold `height` padded rows
+    pub fn new(payload: Vec<u8>, width: usize, height: usize) -> Result<Self, ScreenshotError> {
+        Self::from_raw(&payload, width, height, ColorType::Rgba, &ByteSource::WGPU)
+    }
+
+    /// Create a new [`Screenshot`] object from raw bytes, converting them to unpadded RGBA
+    ///
+    /// Fails with [`ScreenshotError::Length`] unless `payload` holds exactly `height` rows of
+    /// `width` pixels of `color_type`, padded as `source` pads them
+    pub fn from_raw(
+        payload: &[u8],
+        width: usize,
+        height: usize,
+        color_type: ColorType,
+        source: &ByteSource,
+    ) -> Result<Self, ScreenshotError> {
+        let bytes_per_pixel = color_type.bytes_per_pixel();
+        let unpadded_bytes_per_row = width * bytes_per_pixel;
+        let padded_bytes_per_row = source.padded_bytes_per_row(unpadded_bytes_per_row);
+        if payload.len() != padded_bytes_per_row * height {
+            return Err(ScreenshotError::Length {
+                expected: padded_bytes_per_row * height,
+                actual: payload.len(),
+            });
+        }
+
+        let mut rgba = Vec::with_capacity(width * height * BYTES_PER_PIXEL);
+        if padded_bytes_per_row != 0 {
+            for row in payload.chunks(padded_bytes_per_row).take(height) {
+                for pixel in row[..unpadded_bytes_per_row].chunks(bytes_per_pixel) {
+                    match color_type {
+                        ColorType::Rgba => rgba.extend_from_slice(pixel),
+                        ColorType::Rgb => {
+                            rgba.extend_from_slice(&[pixel[0], pixel[1], pixel[2], 0xff]);
+                        }
+                        ColorType::Grayscale => {
+                            rgba.extend_from_slice(&[pixel[0], pixel[0], pixel[0], 0xff]);
+                        }
+                        ColorType::GrayscaleAlpha => {
+                            rgba.extend_from_slice(&[pixel[0], pixel[0], pixel[0], pixel[1]]);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            payload: Arc::new(rgba),
+            width,
+            height,
+            metadata: BTreeMap::new(),
+        })
     }
 
     /// Creates a [`Screenshot`] object from png
-    pub fn from_png<S: AsRef<std::path::Path>>(path: S) -> Result<Self, Box<dyn Error>> {
-        let decoder = png::Decoder::new(File::open(path)?);
+    ///
+    /// Grayscale, grayscale with alpha, indexed and 16 bit images are converted to RGBA8
+    pub fn from_png<S: AsRef<Path>>(path: S) -> Result<Self, ScreenshotError> {
+        Self::decode_png(File::open(path)?)
+    }
+
+    /// Decodes the first frame of a png read from `source`
+    pub fn decode_png<R: Read>(source: R) -> Result<Self, ScreenshotError> {
+        let mut decoder = png::Decoder::new(source);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
         let mut reader = decoder.read_info()?;
         let mut payload = vec![0; reader.output_buffer_size()];
         let out = reader.next_frame(&mut payload)?;
+        let (color_type, _) = reader.output_color_type();
 
-        Ok(Self {
-            payload: Arc::new(payload),
-            width: out.width as usize,
-            height: out.height as usize,
-            color_encoding: reader.info().color_type.into(),
-            source_encoding: ByteSource::Png,
-        })
+        let mut screenshot = Self::from_raw(
+            payload.get(..out.buffer_size()).unwrap_or_default(),
+            out.width as usize,
+            out.height as usize,
+            ColorType::try_from(color_type)?,
+            &ByteSource::Png,
+        )?;
+        screenshot.metadata = reader
+            .info()
+            .uncompressed_latin1_text
+            .iter()
+            .map(|chunk| (chunk.keyword.clone(), chunk.text.clone()))
+            .collect();
+
+        Ok(screenshot)
     }
 
-    /// Saves the [`Screenshot`] to the input path
-    pub fn save_image_to_png<S: AsRef<std::path::Path>>(&self, path: S) {
-        let file = std::fs::File::create(path).expect("Path doesn't exist!");
-        self.encode_png(file);
+    /// Saves the [`Screenshot`] to the input path, creating missing parent directories
+    pub fn save_image_to_png<S: AsRef<Path>>(&self, path: S) -> Result<(), ScreenshotError> {
+        self.encode_png(create_file(path.as_ref())?)
     }
 
-    fn encode_png<W: Write>(&self, buffer: W) {
+    /// Encodes the [`Screenshot`] as an RGBA8 png and writes it to `buffer`
+    pub fn encode_png<W: Write>(&self, buffer: W) -> Result<(), ScreenshotError> {
         let mut png_encoder = png::Encoder::new(buffer, self.width as u32, self.height as u32);
         png_encoder.set_depth(png::BitDepth::Eight);
-        png_encoder.set_color(self.color_encoding.into());
+        png_encoder.set_color(png::ColorType::Rgba);
+        for (key, value) in &self.metadata {
+            png_encoder.add_text_chunk(key.clone(), value.clone())?;
+        }
 
-        let bytes_per_pixel = match self.color_encoding {
-            ColorType::Rgba => std::mem::size_of::<u32>(),
-            ColorType::Rgb => std::mem::size_of::<u8>() * 3,
-        };
+        let mut png_writer = png_encoder.write_header()?;
+        png_writer.write_image_data(&self.payload)?;
+        png_writer.finish()?;
 
-        let unpadded_bytes_per_row = self.width * bytes_per_pixel;
-        let align = match self.source_encoding {
-            ByteSource::WGPU => wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize,
-            ByteSource::Png => 0,
-        };
+        Ok(())
+    }
 
-        let padded_bytes_per_row_padding = if align != 0 {
-            (align - unpadded_bytes_per_row % align) % align
+    /// Saves the [`Screenshot`] to the input path in `format`, creating missing parent directories
+    pub fn save<S: AsRef<Path>, F: ImageFormat + ?Sized>(
+        &self,
+        path: S,
+        format: &F,
+    ) -> Result<(), ScreenshotError> {
+        let mut file = create_file(path.as_ref())?;
+        format.encode(self, &mut file)
+    }
+
+    /// Creates a [`Screenshot`] object from a file in `format`
+    pub fn load<S: AsRef<Path>, F: ImageFormat + ?Sized>(
+        path: S,
+        format: &F,
+    ) -> Result<Self, ScreenshotError> {
+        format.decode(&mut File::open(path)?)
+    }
+
+    /// Describes how the screenshot was produced, see [`metadata`] for well-known keys. Saved to
+    /// and loaded from png tEXt chunks
+    #[must_use]
+    pub const fn metadata(&self) -> &BTreeMap<String, String> {
+        &self.metadata
+    }
+
+    /// Sets a metadata entry; keys must be 1 to 79 Latin-1 characters to be saved to png
+    #[must_use]
+    pub fn with_metadata<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        let _ = self.metadata.insert(key.into(), value.into());
+
+        self
+    }
+
+    /// Keys of [`metadata::ENVIRONMENT`] that both screenshots carry, but with different values
+    #[must_use]
+    pub fn metadata_mismatches(&self, other: &Self) -> Vec<&'static str> {
+        metadata::ENVIRONMENT
+            .iter()
+            .copied()
+            .filter(|key| {
+                matches!(
+                    (self.metadata.get(*key), other.metadata.get(*key)),
+                    (Some(a), Some(b)) if a != b
+                )
+            })
+            .collect()
+    }
+
+    /// Describes how the environments `self` and `other` were rendered in differ, e.g.
+    /// `Window size 800x800 and 390x844`. Returns `None` if no [`metadata::ENVIRONMENT`] key
+    /// differs
+    #[must_use]
+    pub fn metadata_mismatch(&self, other: &Self) -> Option<String> {
+        let mismatches: Vec<String> = self
+            .metadata_mismatches(other)
+            .into_iter()
+            .filter_map(|key| {
+                let (a, b) = (self.metadata.get(key)?, other.metadata.get(key)?);
+                Some(format!("{} {} and {}", key, a, b))
+            })
+            .collect();
+
+        if mismatches.is_empty() {
+            None
         } else {
-            0
+            Some(mismatches.join(", "))
+        }
+    }
+
+    /// Describes the environment the screenshot was rendered in, e.g. `Window size 800x800,
+    /// Backend wgpu`; empty if it carries no [`metadata::ENVIRONMENT`] keys
+    pub(crate) fn environment(&self) -> String {
+        metadata::ENVIRONMENT
+            .iter()
+            .filter_map(|key| Some(format!("{} {}", key, self.metadata.get(*key)?)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Pixels of the image as unpadded rows of RGBA8
+    #[must_use]
+    pub fn as_rgba(&self) -> &[u8] {
+        &self.payload
+    }
+
+    /// Number of bytes in a row of pixels
+    const fn bytes_per_row(&self) -> usize {
+        self.width * BYTES_PER_PIXEL
+    }
+
+    /// Clamps a region in pixels to the bounds of the image
+    fn clamp(&self, region: Rectangle<u32>) -> Rectangle<usize> {
+        let x = (region.x as usize).min(self.width);
+        let y = (region.y as usize).min(self.height);
+        Rectangle {
+            x,
+            y,
+            width: (region.width as usize).min(self.width - x),
+            height: (region.height as usize).min(self.height - y),
+        }
+    }
+
+    /// Creates a new [`Screenshot`] containing only `region` of this one. The region is measured
+    /// in pixels and clamped to the bounds of the image
+    #[must_use]
+    pub fn crop(&self, region: Rectangle<u32>) -> Self {
+        let region = self.clamp(region);
+        let columns = region.x * BYTES_PER_PIXEL..(region.x + region.width) * BYTES_PER_PIXEL;
+
+        let payload: Vec<u8> = self
+            .rows()
+            .skip(region.y)
+            .take(region.height)
+            .flat_map(|row| row[columns.clone()].iter().copied())
+            .collect();
+
+        Self {
+            payload: Arc::new(payload),
+            width: region.width,
+            height: region.height,
+            metadata: self.metadata.clone(),
+        }
+    }
+
+    /// Creates a new [`Screenshot`] with every region in `regions` painted with [`MASK_COLOR`].
+    /// Regions are measured in pixels and clamped to the bounds of the image.
+    ///
+    /// Two screenshots that were masked with the same regions compare equal if they only differ
+    /// inside of those regions
+    #[must_use]
+    pub fn mask(&self, regions: &[Rectangle<u32>]) -> Self {
+        self.paint(regions, MASK_COLOR)
+    }
+
+    /// Creates a new [`Screenshot`] with a crosshair painted with [`CURSOR_COLOR`] centered on the
+    /// pixel at `x` and `y`, marking the position of the mouse cursor
+    #[must_use]
+    pub fn paint_cursor(&self, x: u32, y: u32) -> Self {
+        let horizontal = Rectangle {
+            x: x.saturating_sub(CURSOR_RADIUS),
+            y,
+            width: x.min(CURSOR_RADIUS) + CURSOR_RADIUS + 1,
+            height: 1,
         };
+        let vertical = Rectangle {
+            x,
+            y: y.saturating_sub(CURSOR_RADIUS),
+            width: 1,
+            height: y.min(CURSOR_RADIUS) + CURSOR_RADIUS + 1,
+        };
+
+        self.paint(&[horizontal, vertical], CURSOR_COLOR)
+    }
+
+    /// Creates a new [`Screenshot`] with every region in `regions` painted with `color`, clamped
+    /// to the bounds of the image
+    fn paint(&self, regions: &[Rectangle<u32>], color: [u8; 4]) -> Self {
+        let bytes_per_row = self.bytes_per_row();
+        let mut payload = self.payload.to_vec();
+
+        for region in regions {
+            let region = self.clamp(*region);
+            for row in region.y..region.y + region.height {
+                let start = row * bytes_per_row + region.x * BYTES_PER_PIXEL;
+                for pixel in payload[start..start + region.width * BYTES_PER_PIXEL]
+                    .chunks_mut(BYTES_PER_PIXEL)
+                {
+                    pixel.copy_from_slice(&color);
+                }
+            }
+        }
+
+        Self {
+            payload: Arc::new(payload),
+            width: self.width,
+            height: self.height,
+            metadata: self.metadata.clone(),
+        }
+    }
+
+    /// Compares two screenshots, ignoring any differences inside of `regions`
+    #[must_use]
+    pub fn eq_masked(&self, other: &Self, regions: &[Rectangle<u32>]) -> bool {
+        self.warn_on_metadata_mismatch(other);
+        self.mask(regions) == other.mask(regions)
+    }
+
+    /// Rows of pixels. Each row holds [`Screenshot::width`] RGBA pixels
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        self.payload
+            .chunks(self.bytes_per_row().max(1))
+            .take(self.height)
+    }
+
+    /// Pixels of the image in row-major order
+    pub(crate) fn rgba_pixels(&self) -> Vec<[u8; 4]> {
+        self.payload
+            .chunks(BYTES_PER_PIXEL)
+            .map(|pixel| [pixel[0], pixel[1], pixel[2], pixel[3]])
+            .collect()
+    }
+
+    /// Width of the image in pixels
+    #[must_use]
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Height of the image in pixels
+    #[must_use]
+    pub const fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The pixel at column `x` and row `y` as RGBA
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pixel lies outside of the image
+    #[must_use]
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        assert!(
+            x < self.width && y < self.height,
+            "pixel ({}, {}) is outside of a {}x{} screenshot",
+            x,
+            y,
+            self.width,
+            self.height
+        );
+        let start = y * self.bytes_per_row() + x * BYTES_PER_PIXEL;
+        let pixel = &self.payload[start..start + BYTES_PER_PIXEL];
+
+        [pixel[0], pixel[1], pixel[2], pixel[3]]
+    }
+
+    /// RGBA pixels inside of `region`, which is clamped to the bounds of the image
+    fn region_pixels(&self, region: Rectangle<u32>) -> impl Iterator<Item = [u8; 4]> + '_ {
+        let region = self.clamp(region);
+
+        (region.y..region.y + region.height)
+            .flat_map(move |y| (region.x..region.x + region.width).map(move |x| self.pixel(x, y)))
+    }
+
+    /// The color of every pixel inside of `region`, if they all share one. Returns `None` if the
+    /// region is empty or holds more than one color
+    #[must_use]
+    pub fn uniform_color(&self, region: Rectangle<u32>) -> Option<[u8; 4]> {
+        let mut pixels = self.region_pixels(region);
+        let first = pixels.next()?;
 
-        let padded_bytes_per_row = unpadded_bytes_per_row + padded_bytes_per_row_padding;
+        if pixels.all(|pixel| pixel == first) {
+            Some(first)
+        } else {
+            None
+        }
+    }
 
-        let mut png_writer_z = png_encoder.write_header().expect("Writing buffer failed");
-        let mut png_writer = png_writer_z
-            .stream_writer_with_size(unpadded_bytes_per_row as usize)
-            .expect("Creating stream failed");
+    /// Returns true if every pixel inside of `region` has the color `color`, given as RGBA
+    #[must_use]
+    pub fn is_uniform(&self, region: Rectangle<u32>, color: [u8; 4]) -> bool {
+        self.uniform_color(region) == Some(color)
+    }
 
-        // from the padded_buffer we write just the unpadded bytes into the image
-        for chunk in self.payload.chunks(padded_bytes_per_row) {
-            png_writer
-                .write_all(&chunk[..unpadded_bytes_per_row])
-                .unwrap();
+    /// The most common color inside of `region` as RGBA, or `None` if the region is empty. Ties
+    /// are broken in favour of the color found first
+    #[must_use]
+    pub fn dominant_color(&self, region: Rectangle<u32>) -> Option<[u8; 4]> {
+        let mut counts: HashMap<[u8; 4], (usize, usize)> = HashMap::new();
+        for (index, pixel) in self.region_pixels(region).enumerate() {
+            counts.entry(pixel).or_insert((0, index)).0 += 1;
         }
 
-        png_writer.finish().expect("Png writer finish failed");
+        counts
+            .into_iter()
+            .max_by(|(_, (count_a, first_a)), (_, (count_b, first_b))| {
+                count_a.cmp(count_b).then(first_b.cmp(first_a))
+            })
+            .map(|(color, _)| color)
+    }
+
+    /// Compares two screenshots perceptually. Returns `None` if their dimensions differ
+    #[must_use]
+    pub fn perceptual_diff(&self, other: &Self, settings: &Perceptual) -> Option<DiffReport> {
+        diff::compare(self, other, settings)
+    }
+
+    /// Returns true if two screenshots have the same dimensions and do not differ perceptibly
+    /// according to `settings`
+    #[must_use]
+    pub fn perceptually_eq(&self, other: &Self, settings: &Perceptual) -> bool {
+        self.warn_on_metadata_mismatch(other);
+        self.perceptual_diff(other, settings)
+            .map_or(false, |report| report.score() <= settings.max_diff_ratio)
+    }
+
+    /// Prints a warning if `other`, usually a golden, was rendered in a different environment
+    #[allow(clippy::print_stderr)]
+    fn warn_on_metadata_mismatch(&self, other: &Self) {
+        if let Some(mismatch) = self.metadata_mismatch(other) {
+            eprintln!(
+                "warning: comparing screenshots rendered in different environments: {}",
+                mismatch
+            );
+        }
+    }
+
+    /// Width and height of the image in pixels
+    pub(crate) const fn dimensions(&self) -> (usize, usize) {
+        (self.width, self.height)
     }
 
     /// This does a round-trip from raw data-> png data -> back to "raw frame data;
-    /// The motivation for this is that the raw pixel data of a screenshot won't be equivalent to what the data in a png frame will be
-    /// due to padding or other encoding limitations
+    ///
+    /// Screenshots are normalized when they are created, so this no longer changes the pixel data.
+    /// It is kept to check that a screenshot survives png encoding
+    ///
+    /// # Panics
+    ///
+    /// Panics if encoding to or decoding from memory fails, which indicates a bug in the png crate
     #[must_use]
     pub fn encode_png_frame(self) -> Self {
         let mut out_vec = vec![];
-        self.encode_png(&mut out_vec);
+        self.encode_png(&mut out_vec)
+            .expect("Encoding png in memory failed");
 
-        let decoder = png::Decoder::new(out_vec.as_slice());
-        let mut reader = decoder
-            .read_info()
-            .expect("Decoder failed reading info, png may be malformed");
-        let mut payload = vec![0; reader.output_buffer_size()];
-        let out = reader
-            .next_frame(&mut payload)
-            .expect("Reading frame failed");
-        Self {
-            payload: Arc::new(payload),
-            width: out.width as usize,
-            height: out.height as usize,
-            color_encoding: reader.info().color_type.into(),
-            source_encoding: ByteSource::Png,
-        }
+        Self::decode_png(out_vec.as_slice()).expect("Decoding png from memory failed")
     }
 }
 
@@ -170,9 +695,12 @@ mod test {
     #[test]
     fn round_trip() {
         let payload = vec![0xfe; 4 * 512 * 512];
-        let ss = Screenshot::new(payload, 512, 512).encode_png_frame();
+        let ss = Screenshot::new(payload, 512, 512)
+            .expect("payload matches dimensions")
+            .encode_png_frame();
         let temp_png = tempfile::NamedTempFile::new().expect("tempfile creation failed");
-        ss.save_image_to_png(temp_png.path());
+        ss.save_image_to_png(temp_png.path())
+            .expect("Saving png failed");
         let ss_from_file = Screenshot::from_png(temp_png.path()).expect("Decoder fail");
         assert_eq!(ss, ss_from_file);
     }
@@ -180,11 +708,12 @@ mod test {
     #[test]
     fn round_trip_rgb() {
         let payload = vec![0xfe; 3 * 512 * 512];
-        let ss = Screenshot::new(payload, 512, 512)
-            .color_encoding(ColorType::Rgb)
+        let ss = Screenshot::from_raw(&payload, 512, 512, ColorType::Rgb, &ByteSource::Png)
+            .expect("payload matches dimensions")
             .encode_png_frame();
         let temp_png = tempfile::NamedTempFile::new().expect("tempfile creation failed");
-        ss.save_image_to_png(temp_png.path());
+        ss.save_image_to_png(temp_png.path())
+            .expect("Saving png failed");
         let ss_from_file = Screenshot::from_png(temp_png.path()).expect("Decoder fail");
         //let temp_png2 = tempfile::NamedTempFile::new().expect("tempfile creation failed");
 
@@ -194,4 +723,246 @@ mod test {
 
         assert_eq!(ss, ss_from_file);
     }
+
+    #[test]
+    fn crop_and_mask() {
+        let mut payload = vec![0x00; 4 * 64 * 64];
+        payload[..4].copy_from_slice(&[0x10, 0x20, 0x30, 0x40]);
+        let ss = Screenshot::new(payload, 64, 64)
+            .expect("payload matches dimensions")
+            .encode_png_frame();
+
+        let cropped = ss.crop(Rectangle {
+            x: 0,
+            y: 0,
+            width: 2,
+            height: 100,
+        });
+        assert_eq!(cropped.dimensions(), (2, 64));
+        assert_eq!(
+            cropped.rows().next(),
+            Some(&[0x10, 0x20, 0x30, 0x40, 0, 0, 0, 0][..])
+        );
+
+        let corner = Rectangle {
+            x: 0,
+            y: 0,
+            width: 1,
+            height: 1,
+        };
+        let blank = Screenshot::new(vec![0x00; 4 * 64 * 64], 64, 64)
+            .expect("payload matches dimensions")
+            .encode_png_frame();
+        assert_ne!(ss, blank);
+        assert!(ss.eq_masked(&blank, &[corner]));
+        assert_eq!(
+            ss.mask(&[corner]).rows().next().map(|row| &row[..4]),
+            Some(&MASK_COLOR[..])
+        );
+    }
+
+    #[test]
+    fn cursor_marker() {
+        let blank =
+            Screenshot::new(vec![0x00; 4 * 64 * 64], 64, 64).expect("payload matches dimensions");
+        let marked = blank.paint_cursor(2, 40);
+
+        assert_eq!(marked.pixel(2, 40), CURSOR_COLOR);
+        assert_eq!(marked.pixel(0, 40), CURSOR_COLOR);
+        assert_eq!(marked.pixel(8, 40), CURSOR_COLOR);
+        assert_eq!(marked.pixel(9, 40), [0x00; 4]);
+        assert_eq!(marked.pixel(2, 34), CURSOR_COLOR);
+        assert_eq!(marked.pixel(2, 47), [0x00; 4]);
+        assert_eq!(marked.pixel(3, 41), [0x00; 4]);
+        assert_eq!(blank.paint_cursor(100, 100), blank);
+    }
+
+    #[test]
+    fn pixel_accessors() {
+        //a 60 pixel wide WGPU payload has padded rows
+        let row_bytes = 256;
+        let mut payload = vec![0x00; row_bytes * 4];
+        for row in payload.chunks_mut(row_bytes) {
+            for pixel in row[..4 * 60].chunks_mut(4) {
+                pixel.copy_from_slice(&[0x10, 0x20, 0x30, 0xff]);
+            }
+        }
+        payload[row_bytes * 3..row_bytes * 3 + 4].copy_from_slice(&[0xff, 0x00, 0x00, 0xff]);
+        let ss = Screenshot::new(payload, 60, 4).expect("payload matches dimensions");
+
+        assert_eq!((ss.width(), ss.height()), (60, 4));
+        assert!(ss.rows().all(|row| row.len() == 4 * 60));
+        assert_eq!(ss.pixel(0, 3), [0xff, 0x00, 0x00, 0xff]);
+        assert_eq!(ss.pixel(59, 3), [0x10, 0x20, 0x30, 0xff]);
+
+        let everything = Rectangle {
+            x: 0,
+            y: 0,
+            width: 60,
+            height: 4,
+        };
+        let top = Rectangle {
+            height: 3,
+            ..everything
+        };
+        assert_eq!(ss.uniform_color(everything), None);
+        assert!(ss.is_uniform(top, [0x10, 0x20, 0x30, 0xff]));
+        assert_eq!(
+            ss.dominant_color(everything),
+            Some([0x10, 0x20, 0x30, 0xff])
+        );
+    }
+
+    #[test]
+    fn equal_across_byte_sources() {
+        //60 RGBA pixels need 240 bytes, which WGPU pads to 256
+        let mut padded = vec![0x00; 256 * 2];
+        let mut unpadded = Vec::new();
+        for (index, row) in padded.chunks_mut(256).enumerate() {
+            for pixel in row[..4 * 60].chunks_mut(4) {
+                pixel.copy_from_slice(&[index as u8, 0x20, 0x30, 0xff]);
+                unpadded.extend_from_slice(pixel);
+            }
+        }
+        let rgb: Vec<u8> = unpadded
+            .chunks(4)
+            .flat_map(|pixel| pixel[..3].to_vec())
+            .collect();
+
+        let from_wgpu = Screenshot::new(padded, 60, 2).expect("payload matches dimensions");
+        let from_png = Screenshot::from_raw(&unpadded, 60, 2, ColorType::Rgba, &ByteSource::Png)
+            .expect("payload matches dimensions");
+        let from_rgb = Screenshot::from_raw(&rgb, 60, 2, ColorType::Rgb, &ByteSource::Png)
+            .expect("payload matches dimensions");
+        assert_eq!(from_wgpu, from_png);
+        assert_eq!(from_wgpu, from_rgb);
+
+        let hash = |ss: &Screenshot| {
+            use std::hash::{Hash, Hasher};
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            ss.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&from_wgpu), hash(&from_png));
+    }
+
+    #[test]
+    fn rejects_mismatched_lengths() {
+        assert!(matches!(
+            Screenshot::from_raw(&[0xfe; 4 * 60], 60, 2, ColorType::Rgba, &ByteSource::Png),
+            Err(ScreenshotError::Length {
+                expected: 480,
+                actual: 240
+            })
+        ));
+        //unpadded rows are too short for a WGPU readback
+        assert!(matches!(
+            Screenshot::new(vec![0xfe; 4 * 60 * 2], 60, 2),
+            Err(ScreenshotError::Length {
+                expected: 512,
+                actual: 480
+            })
+        ));
+    }
+
+    /// Encodes a 2x1 png with the given color type and bit depth
+    fn encode_raw(
+        color: png::ColorType,
+        depth: png::BitDepth,
+        palette: Option<Vec<u8>>,
+        data: &[u8],
+    ) -> Vec<u8> {
+        let mut out_vec = vec![];
+        let mut encoder = png::Encoder::new(&mut out_vec, 2, 1);
+        encoder.set_color(color);
+        encoder.set_depth(depth);
+        if let Some(palette) = palette {
+            encoder.set_palette(palette);
+        }
+        let mut writer = encoder.write_header().expect("Writing header failed");
+        writer.write_image_data(data).expect("Writing data failed");
+        writer.finish().expect("Finishing png failed");
+        out_vec
+    }
+
+    #[test]
+    fn decode_other_color_types() {
+        let expected = [[0x10, 0x10, 0x10, 0xff], [0x80, 0x80, 0x80, 0xff]];
+        let pixels = |png: Vec<u8>| {
+            let ss = Screenshot::decode_png(png.as_slice()).expect("Decoding failed");
+            [ss.pixel(0, 0), ss.pixel(1, 0)]
+        };
+
+        let gray = encode_raw(
+            png::ColorType::Grayscale,
+            png::BitDepth::Eight,
+            None,
+            &[0x10, 0x80],
+        );
+        assert_eq!(pixels(gray), expected);
+
+        let gray_alpha = encode_raw(
+            png::ColorType::GrayscaleAlpha,
+            png::BitDepth::Eight,
+            None,
+            &[0x10, 0xff, 0x80, 0x40],
+        );
+        assert_eq!(
+            pixels(gray_alpha),
+            [[0x10, 0x10, 0x10, 0xff], [0x80, 0x80, 0x80, 0x40]]
+        );
+
+        let indexed = encode_raw(
+            png::ColorType::Indexed,
+            png::BitDepth::Eight,
+            Some(vec![0x10, 0x10, 0x10, 0x80, 0x80, 0x80]),
+            &[0, 1],
+        );
+        assert_eq!(pixels(indexed), expected);
+
+        let sixteen_bit = encode_raw(
+            png::ColorType::Grayscale,
+            png::BitDepth::Sixteen,
+            None,
+            &[0x10, 0x00, 0x80, 0xff],
+        );
+        assert_eq!(pixels(sixteen_bit), expected);
+    }
+
+    #[test]
+    fn save_creates_directories() {
+        let dir = tempfile::tempdir().expect("tempdir creation failed");
+        let path = dir.path().join("nested").join("golden").join("ss.png");
+        let ss =
+            Screenshot::new(vec![0xfe; 4 * 64 * 64], 64, 64).expect("payload matches dimensions");
+
+        ss.save_image_to_png(&path).expect("Saving png failed");
+        assert_eq!(Screenshot::from_png(&path).expect("Decoder fail"), ss);
+        assert!(matches!(
+            Screenshot::from_png(dir.path().join("missing.png")),
+            Err(ScreenshotError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn metadata_round_trip() {
+        let ss = Screenshot::new(vec![0xfe; 4 * 64 * 64], 64, 64)
+            .expect("payload matches dimensions")
+            .with_metadata(metadata::TRACE, "increments")
+            .with_metadata(metadata::WINDOW_SIZE, "800x800");
+        let decoded = ss.clone().encode_png_frame();
+        assert_eq!(decoded.metadata(), ss.metadata());
+        assert_eq!(ss.metadata_mismatch(&decoded), None);
+
+        let resized = decoded.with_metadata(metadata::WINDOW_SIZE, "390x844");
+        assert_eq!(
+            ss.metadata_mismatches(&resized),
+            vec![metadata::WINDOW_SIZE]
+        );
+        assert_eq!(
+            ss.metadata_mismatch(&resized).as_deref(),
+            Some("Window size 800x800 and 390x844")
+        );
+        assert_eq!(ss, resized);
+    }
 }
@@ -0,0 +1,28 @@
+//! In-memory clipboard of headless runs
+use iced_native::clipboard::Clipboard;
+
+/// Clipboard shared by the widgets of a trace and the clipboard actions of its commands
+///
+/// Each trace starts with an empty clipboard; it never touches the clipboard of the system
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct VirtualClipboard {
+    /// Last written contents
+    contents: Option<String>,
+}
+
+impl VirtualClipboard {
+    /// Current contents, if anything was written
+    pub(crate) fn contents(&self) -> Option<&str> {
+        self.contents.as_deref()
+    }
+}
+
+impl Clipboard for VirtualClipboard {
+    fn read(&self) -> Option<String> {
+        self.contents.clone()
+    }
+
+    fn write(&mut self, contents: String) {
+        self.contents = Some(contents);
+    }
+}
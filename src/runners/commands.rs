@@ -0,0 +1,241 @@
+//! Stand-ins for the futures of [`Command`]s returned by an application
+//!
+//! Futures of commands returned by the application are discarded unless a [`CommandStub`]
+//! registered with [`Runner::stub`] matches them. The futures of a matching command are dropped
+//! without being polled, and the canned message of the first matching stub is delivered to
+//! `update` in their place, so
+//! the whole `update` → command → message loop runs without reaching real services. Clipboard
+//! actions read and write the trace's virtual clipboard, and window actions are collected for the
+//! trace's [`WindowHistory`](super::WindowHistory).
+//!
+//! [`Command`]: iced::Command
+//! [`Runner::stub`]: super::Runner::stub
+use super::clipboard::VirtualClipboard;
+use super::messages::{LoggedMessage, MessageLog, MessageSource};
+use super::window::WindowChange;
+use super::AppHarness;
+use crate::runtime::Command;
+use iced_native::clipboard::{self, Clipboard};
+use iced_native::command::Action;
+use iced_native::Program;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Answers the futures of commands with a canned message
+#[allow(missing_debug_implementations)]
+pub struct CommandStub<A: iced::Application> {
+    /// Name used in reports
+    name: String,
+    /// Selects the commands this stub answers
+    trigger: Trigger<A>,
+    /// Produces the message delivered in place of the output of the futures
+    response: Box<dyn Fn() -> A::Message>,
+    /// Virtual time between the command being returned and the response being delivered
+    delay: Duration,
+}
+
+/// Selects the commands a [`CommandStub`] answers
+enum Trigger<A: iced::Application> {
+    /// The command returned by [`Application::new`](iced::Application::new)
+    Init,
+    /// Commands returned by `update` for messages accepted by the predicate
+    Message(Box<dyn Fn(&A::Message) -> bool>),
+}
+
+impl<A: iced::Application> CommandStub<A> {
+    /// Answers commands returned by `update` for messages accepted by `trigger` with the message
+    /// produced by `response`, e.g.
+    /// `CommandStub::on("load", |m| matches!(m, Message::Load), || Message::Loaded(Ok(42)))`
+    pub fn on<S: Into<String>>(
+        name: S,
+        trigger: impl Fn(&A::Message) -> bool + 'static,
+        response: impl Fn() -> A::Message + 'static,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            trigger: Trigger::Message(Box::new(trigger)),
+            response: Box::new(response),
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// Answers the command returned by [`Application::new`](iced::Application::new) with the
+    /// message produced by `response`
+    pub fn on_init<S: Into<String>>(name: S, response: impl Fn() -> A::Message + 'static) -> Self {
+        Self {
+            name: name.into(),
+            trigger: Trigger::Init,
+            response: Box::new(response),
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// Delivers the response once the trace's [`TraceEvent::Duration`]s add up to `delay` after
+    /// the command was returned, instead of immediately
+    ///
+    /// [`TraceEvent::Duration`]: crate::TraceEvent::Duration
+    #[must_use]
+    pub const fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+
+        self
+    }
+
+    /// Name of the stub
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Delivers messages to an application, answering the commands it returns with [`CommandStub`]s
+pub(crate) struct CommandLoop<'a, A: iced::Application> {
+    /// Stubs commands are answered with
+    stubs: &'a [CommandStub<A>],
+    /// Virtual time since the trace started; the sum of its durations
+    clock: Duration,
+    /// Responses of delayed stubs and the virtual time they are delivered at
+    delayed: Vec<(Duration, MessageSource, A::Message)>,
+    /// Copies delivered messages into the log, if the runner keeps them
+    keep: Option<fn(&A::Message) -> A::Message>,
+    /// Every delivered message
+    pub(crate) messages: MessageLog<A::Message>,
+    /// Clipboard read and written by clipboard actions
+    pub(crate) clipboard: VirtualClipboard,
+    /// Window actions returned since they were last taken
+    window_actions: Vec<WindowChange>,
+}
+
+impl<'a, A: iced::Application> CommandLoop<'a, A> {
+    /// Create a new [`CommandLoop`] answering commands with `stubs`, logging copies made by `keep`
+    pub(crate) fn new(
+        stubs: &'a [CommandStub<A>],
+        keep: Option<fn(&A::Message) -> A::Message>,
+    ) -> Self {
+        Self {
+            stubs,
+            clock: Duration::ZERO,
+            delayed: Vec::new(),
+            keep,
+            messages: MessageLog::default(),
+            clipboard: VirtualClipboard::default(),
+            window_actions: Vec::new(),
+        }
+    }
+
+    /// Takes the window actions returned since the last call
+    pub(crate) fn take_window_actions(&mut self) -> Vec<WindowChange> {
+        std::mem::take(&mut self.window_actions)
+    }
+
+    /// Answers the command returned by [`Application::new`](iced::Application::new)
+    pub(crate) fn init(&mut self, application: &mut AppHarness<A>, command: Command<A::Message>) {
+        let stubs = self.stubs;
+        let triggered = stubs
+            .iter()
+            .find(|stub| matches!(stub.trigger, Trigger::Init));
+        let mut queue = VecDeque::new();
+        self.answer(triggered, command, &mut queue);
+        self.deliver(application, None, queue);
+    }
+
+    /// Delivers `messages` from `source` during `step`, followed by the responses to the
+    /// commands they cause
+    pub(crate) fn update(
+        &mut self,
+        application: &mut AppHarness<A>,
+        step: usize,
+        source: MessageSource,
+        messages: impl IntoIterator<Item = A::Message>,
+    ) {
+        let queue = messages
+            .into_iter()
+            .map(|message| (source.clone(), message))
+            .collect();
+        self.deliver(application, Some(step), queue);
+    }
+
+    /// Advances the virtual clock by `duration` and delivers the delayed responses that became
+    /// due during `step`. Returns true if any were delivered
+    pub(crate) fn advance(
+        &mut self,
+        application: &mut AppHarness<A>,
+        step: usize,
+        duration: Duration,
+    ) -> bool {
+        self.clock += duration;
+        let clock = self.clock;
+        let (mut due, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.delayed)
+            .into_iter()
+            .partition(|(at, _, _)| *at <= clock);
+        self.delayed = pending;
+        due.sort_by_key(|(at, _, _)| *at);
+
+        let delivered = !due.is_empty();
+        self.deliver(
+            application,
+            Some(step),
+            due.into_iter()
+                .map(|(_, source, message)| (source, message))
+                .collect(),
+        );
+        delivered
+    }
+
+    /// Delivers and logs every message of `queue` in order, queueing the immediate responses to
+    /// the commands they cause
+    fn deliver(
+        &mut self,
+        application: &mut AppHarness<A>,
+        step: Option<usize>,
+        mut queue: VecDeque<(MessageSource, A::Message)>,
+    ) {
+        let stubs = self.stubs;
+        while let Some((source, message)) = queue.pop_front() {
+            let triggered = stubs.iter().find(|stub| match &stub.trigger {
+                Trigger::Init => false,
+                Trigger::Message(trigger) => trigger(&message),
+            });
+            self.messages
+                .push(LoggedMessage::new(step, source, &message, self.keep));
+            let command = application.update(message);
+            self.answer(triggered, command, &mut queue);
+        }
+    }
+
+    /// Performs the clipboard actions of `command` and drops its futures, answering them with the
+    /// response of `triggered`. Commands without futures are not answered
+    fn answer(
+        &mut self,
+        triggered: Option<&CommandStub<A>>,
+        command: Command<A::Message>,
+        queue: &mut VecDeque<(MessageSource, A::Message)>,
+    ) {
+        let mut performs = 0;
+        for action in command.actions() {
+            match action {
+                Action::Future(_) => performs += 1,
+                Action::Clipboard(clipboard::Action::Read(read)) => {
+                    queue.push_back((MessageSource::Clipboard, read(self.clipboard.read())));
+                }
+                Action::Clipboard(clipboard::Action::Write(contents)) => {
+                    self.clipboard.write(contents);
+                }
+                Action::Window(action) => self.window_actions.push(action.into()),
+            }
+        }
+        let stub = match triggered {
+            Some(stub) if performs > 0 => stub,
+            _ => return,
+        };
+
+        let source = MessageSource::Stub(stub.name.clone());
+        let response = (stub.response)();
+        if stub.delay.is_zero() {
+            queue.push_back((source, response));
+        } else {
+            self.delayed
+                .push((self.clock + stub.delay, source, response));
+        }
+    }
+}
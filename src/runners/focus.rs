@@ -0,0 +1,107 @@
+//! Keyboard focus of text inputs
+//!
+//! iced neither exposes which widget is focused through the widget tree nor moves focus when Tab
+//! is pressed. Text inputs keep their focus in a `text_input::State` owned by the application, so
+//! the runner tracks focus through [`Focusable`]s that locate those states, and moves focus between
+//! them itself when a trace presses Tab
+use crate::query::{LayoutSnapshot, Selector};
+use iced_native::widget::text_input;
+use std::cmp::Ordering;
+
+/// A text input that can be focused with Tab: the [`Selector`] of the widget and the state it
+/// keeps its focus in
+///
+/// The selector must match the input whatever it holds. An empty input only draws its
+/// placeholder, so a [`Selector::Text`] of the placeholder stops matching once the input has a
+/// value; select the input by its [`Selector::Path`] instead
+#[allow(missing_debug_implementations)]
+pub struct Focusable<A> {
+    /// Locates the input in the layout
+    pub selector: Selector,
+    /// Borrows the state of the input from the application
+    state: Box<dyn Fn(&mut A) -> &mut text_input::State>,
+}
+
+impl<A> Focusable<A> {
+    /// Create a new [`Focusable`] for the input located by `selector`, whose state is borrowed by
+    /// `state`, e.g. `Focusable::new(Selector::Path(vec![1]), |app: &mut App| &mut app.name_input)`
+    pub fn new(
+        selector: Selector,
+        state: impl Fn(&mut A) -> &mut text_input::State + 'static,
+    ) -> Self {
+        Self {
+            selector,
+            state: Box::new(state),
+        }
+    }
+
+    /// Returns true if the input is focused
+    fn is_focused(&self, application: &mut A) -> bool {
+        (self.state)(application).is_focused()
+    }
+}
+
+/// Selector of the focused input, if any
+pub(crate) fn focused<'a, A>(
+    focusables: &'a [Focusable<A>],
+    application: &mut A,
+) -> Option<&'a Selector> {
+    focusables
+        .iter()
+        .find(|focusable| focusable.is_focused(application))
+        .map(|focusable| &focusable.selector)
+}
+
+/// The inputs laid out in `layout` in tab order: top to bottom, then left to right. Inputs that are
+/// not laid out are skipped
+pub(crate) fn tab_order<'a, A>(
+    focusables: &'a [Focusable<A>],
+    layout: &LayoutSnapshot,
+) -> Vec<&'a Focusable<A>> {
+    let mut located: Vec<_> = focusables
+        .iter()
+        .filter_map(|focusable| {
+            layout
+                .bounds(&focusable.selector)
+                .map(|bounds| (bounds, focusable))
+        })
+        .collect();
+    located.sort_by(|(a, _), (b, _)| {
+        a.y.partial_cmp(&b.y)
+            .unwrap_or(Ordering::Equal)
+            .then(a.x.partial_cmp(&b.x).unwrap_or(Ordering::Equal))
+    });
+
+    located
+        .into_iter()
+        .map(|(_, focusable)| focusable)
+        .collect()
+}
+
+/// Moves focus to the input after the focused one in `order`, or before it if `backwards`,
+/// wrapping around. Without a focused input, the first or last input is focused
+pub(crate) fn move_focus<A>(order: &[&Focusable<A>], application: &mut A, backwards: bool) {
+    let len = order.len();
+    if len == 0 {
+        return;
+    }
+
+    let current = order
+        .iter()
+        .position(|focusable| focusable.is_focused(application));
+    for focusable in order {
+        (focusable.state)(application).unfocus();
+    }
+
+    let next = match (current, backwards) {
+        (None, false) => 0,
+        (None, true) => len - 1,
+        (Some(index), false) => (index + 1) % len,
+        (Some(index), true) => (index + len - 1) % len,
+    };
+    if let Some(focusable) = order.get(next) {
+        let state = (focusable.state)(application);
+        state.focus();
+        state.move_cursor_to_end();
+    }
+}
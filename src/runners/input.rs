@@ -0,0 +1,121 @@
+//! Input events simulated by traces
+use iced::Point;
+use iced_native::keyboard::{self, KeyCode, Modifiers};
+use iced_native::{mouse, Event};
+
+/// Control characters that [`TraceEvent::TypeText`](crate::TraceEvent::TypeText) types as keys
+/// and shortcuts instead of text, e.g. `format!("draft{}{}", keys::SELECT_ALL, keys::PASTE)`
+///
+/// Shortcuts are typed with the platform's command modifier: Ctrl, or Cmd on macOS
+pub mod keys {
+    /// Backspace
+    pub const BACKSPACE: char = '\u{8}';
+    /// Delete
+    pub const DELETE: char = '\u{7f}';
+    /// Enter, which submits a text input
+    pub const ENTER: char = '\n';
+    /// Select all, Ctrl+A
+    pub const SELECT_ALL: char = '\u{1}';
+    /// Copy, Ctrl+C
+    pub const COPY: char = '\u{3}';
+    /// Paste, Ctrl+V
+    pub const PASTE: char = '\u{16}';
+    /// Cut, Ctrl+X
+    pub const CUT: char = '\u{18}';
+}
+
+/// Events of pressing and releasing `key_code` while `modifiers` are held
+pub(crate) fn key_events(key_code: KeyCode, modifiers: Modifiers) -> Vec<Event> {
+    let mut events = vec![
+        keyboard::Event::KeyPressed {
+            key_code,
+            modifiers,
+        },
+        keyboard::Event::KeyReleased {
+            key_code,
+            modifiers,
+        },
+    ];
+    if !modifiers.is_empty() {
+        events.insert(0, keyboard::Event::ModifiersChanged(modifiers));
+        events.push(keyboard::Event::ModifiersChanged(Modifiers::empty()));
+    }
+
+    events.into_iter().map(Event::Keyboard).collect()
+}
+
+/// Position of the virtual cursor before a trace moves it; outside of the window, so that no
+/// widget is hovered
+pub(crate) const CURSOR_OUTSIDE: Point = Point::new(-1.0, -1.0);
+
+/// Event of moving the cursor to `position`
+pub(crate) fn move_events(position: Point) -> Vec<Event> {
+    vec![Event::Mouse(mouse::Event::CursorMoved { position })]
+}
+
+/// Events of moving the cursor to `position` and clicking the left mouse button
+pub(crate) fn click_events(position: Point) -> Vec<Event> {
+    let mut events = move_events(position);
+    events.extend(
+        [
+            mouse::Event::ButtonPressed(mouse::Button::Left),
+            mouse::Event::ButtonReleased(mouse::Button::Left),
+        ]
+        .map(Event::Mouse),
+    );
+
+    events
+}
+
+/// Event of scrolling the mouse wheel by `delta`
+pub(crate) fn scroll_events(delta: mouse::ScrollDelta) -> Vec<Event> {
+    vec![Event::Mouse(mouse::Event::WheelScrolled { delta })]
+}
+
+/// Events of typing `text`; see [`keys`] for the control characters that are typed as keys.
+/// Fails with the first other control character
+pub(crate) fn typing_events(text: &str) -> Result<Vec<Event>, char> {
+    let mut events = Vec::new();
+    for character in text.chars() {
+        let shortcut = |key_code| key_events(key_code, Modifiers::COMMAND);
+        match character {
+            keys::BACKSPACE => events.extend(key_events(KeyCode::Backspace, Modifiers::empty())),
+            keys::DELETE => events.extend(key_events(KeyCode::Delete, Modifiers::empty())),
+            keys::ENTER => events.extend(key_events(KeyCode::Enter, Modifiers::empty())),
+            keys::SELECT_ALL => events.extend(shortcut(KeyCode::A)),
+            keys::COPY => events.extend(shortcut(KeyCode::C)),
+            keys::PASTE => events.extend(shortcut(KeyCode::V)),
+            keys::CUT => events.extend(shortcut(KeyCode::X)),
+            character if character.is_control() => return Err(character),
+            character => events.push(Event::Keyboard(keyboard::Event::CharacterReceived(
+                character,
+            ))),
+        }
+    }
+
+    Ok(events)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn typing() {
+        let events =
+            typing_events(&format!("ab{}{}", keys::BACKSPACE, keys::PASTE)).expect("Typing failed");
+        assert_eq!(events.len(), 2 + 2 + 4);
+        assert_eq!(
+            events[0],
+            Event::Keyboard(keyboard::Event::CharacterReceived('a'))
+        );
+        assert_eq!(
+            events[5],
+            Event::Keyboard(keyboard::Event::KeyPressed {
+                key_code: KeyCode::V,
+                modifiers: Modifiers::COMMAND,
+            })
+        );
+        assert_eq!(typing_events("\u{2}"), Err('\u{2}'));
+    }
+}
@@ -0,0 +1,390 @@
+//! Run the same trace across a matrix of window sizes, scale factors and style variants
+use super::{run_trace, RunOptions, Runner};
+use crate::trace_events::TraceEvent;
+use iced_graphics::Antialiasing;
+use std::any::Any;
+use std::cell::RefCell;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+/// Describes the headless window a trace is executed in
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowConfig {
+    /// Logical size of the window
+    pub size: (u32, u32),
+    /// Scale factor of the window; multiplied with [`Application::scale_factor`]
+    ///
+    /// [`Application::scale_factor`]: iced::Application::scale_factor
+    pub scale_factor: f64,
+    /// Name used in reports and golden file names. Derived from size and scale factor if unset
+    pub name: Option<String>,
+    /// Multisample antialiasing of the renderer; should match the `antialiasing` setting the
+    /// application is run with
+    pub antialiasing: Option<Antialiasing>,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self::new((800, 800))
+    }
+}
+
+impl WindowConfig {
+    /// Create a new [`WindowConfig`] with a logical window size and a scale factor of 1
+    #[must_use]
+    pub const fn new(size: (u32, u32)) -> Self {
+        Self {
+            size,
+            scale_factor: 1.0,
+            name: None,
+            antialiasing: None,
+        }
+    }
+
+    /// Sets the scale factor of the window
+    #[must_use]
+    pub const fn scale_factor(mut self, scale_factor: f64) -> Self {
+        self.scale_factor = scale_factor;
+
+        self
+    }
+
+    /// Sets the multisample antialiasing of the renderer
+    #[must_use]
+    pub const fn antialiasing(mut self, antialiasing: Antialiasing) -> Self {
+        self.antialiasing = Some(antialiasing);
+
+        self
+    }
+
+    /// Sets the name of the configuration, e.g. "phone"
+    #[must_use]
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = Some(name.into());
+
+        self
+    }
+
+    /// Label of the configuration; the name if set, otherwise e.g. `800x600@1.5x`
+    #[must_use]
+    pub fn label(&self) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("{}x{}@{}x", self.size.0, self.size.1, self.scale_factor))
+    }
+
+    /// Suffixes the file name of a golden with the label of this configuration, so that
+    /// `golden/main.png` becomes `golden/main-phone.png`
+    #[must_use]
+    pub fn golden_path(&self, path: &Path) -> PathBuf {
+        suffixed_path(path, &self.label())
+    }
+}
+
+/// A named style of an [`Application`], selected by mutating its state, e.g. a dark theme
+///
+/// [`Application`]: iced::Application
+#[allow(missing_debug_implementations)]
+pub struct StyleVariant<A> {
+    /// Name used in reports and golden file names
+    pub name: String,
+    /// Configures [`Application`] state to use this style; behaves like [`TraceEvent::SetState`]
+    ///
+    /// [`Application`]: iced::Application
+    pub apply: Box<dyn Fn(&mut A)>,
+}
+
+impl<A> StyleVariant<A> {
+    /// Create a new [`StyleVariant`]
+    pub fn new<S: Into<String>>(name: S, apply: impl Fn(&mut A) + 'static) -> Self {
+        Self {
+            name: name.into(),
+            apply: Box::new(apply),
+        }
+    }
+}
+
+/// Appends `-suffix` to the file stem of `path`
+pub(crate) fn suffixed_path(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let file_name = match path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, suffix, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, suffix),
+    };
+
+    path.with_file_name(file_name)
+}
+
+/// Outcome of running a trace in a single [`WindowConfig`] and [`StyleVariant`]
+#[allow(missing_debug_implementations)]
+pub struct MatrixRun<A> {
+    /// The configuration the trace was executed in
+    pub config: WindowConfig,
+    /// Name of the [`StyleVariant`] the trace was executed with, if any
+    pub variant: Option<String>,
+    /// The [`Application`] after the trace completed, or the failure message
+    ///
+    /// [`Application`]: iced::Application
+    pub result: Result<A, String>,
+    /// Graphics adapter the trace was last rendered with, if it rendered anything; see
+    /// [`HeadlessConfig`](crate::rendering::HeadlessConfig)
+    pub adapter: Option<String>,
+}
+
+impl<A> MatrixRun<A> {
+    /// Label of the run; the window label, followed by the variant name if any
+    #[must_use]
+    pub fn label(&self) -> String {
+        run_label(&self.config, self.variant.as_deref())
+    }
+}
+
+/// Label of a run in `config` with the variant named `variant`, if any. Reports show it and the
+/// goldens of the run are suffixed with it, so that a failing run can be traced to its goldens
+fn run_label(config: &WindowConfig, variant: Option<&str>) -> String {
+    match variant {
+        Some(variant) => format!("{}-{}", config.label(), variant),
+        None => config.label(),
+    }
+}
+
+/// Aggregated outcome of [`execute_iced_trace_matrix`] and [`execute_iced_trace_variants`]
+#[allow(missing_debug_implementations)]
+pub struct MatrixReport<A> {
+    /// One entry per configuration, in the order they were provided
+    pub runs: Vec<MatrixRun<A>>,
+}
+
+impl<A> MatrixReport<A> {
+    /// Returns true if the trace passed in every configuration
+    #[must_use]
+    pub fn passed(&self) -> bool {
+        self.runs.iter().all(|run| run.result.is_ok())
+    }
+
+    /// Runs that failed
+    pub fn failures(&self) -> impl Iterator<Item = &MatrixRun<A>> {
+        self.runs.iter().filter(|run| run.result.is_err())
+    }
+
+    /// Returns the [`Application`] of every run keyed by [`MatrixRun::label`], panicking with a
+    /// summary of all failing runs if any failed
+    ///
+    /// [`Application`]: iced::Application
+    #[allow(clippy::panic)]
+    pub fn unwrap_all(self) -> Vec<(String, A)> {
+        if !self.passed() {
+            let summary: Vec<String> = self
+                .failures()
+                .filter_map(|run| {
+                    run.result.as_ref().err().map(|message| match &run.adapter {
+                        Some(adapter) => format!("  {} ({}): {}", run.label(), adapter, message),
+                        None => format!("  {}: {}", run.label(), message),
+                    })
+                })
+                .collect();
+            panic!(
+                "trace failed in {} of {} configurations:\n{}",
+                summary.len(),
+                self.runs.len(),
+                summary.join("\n")
+            );
+        }
+
+        self.runs
+            .into_iter()
+            .filter_map(|run| {
+                let label = run.label();
+                run.result.ok().map(|app| (label, app))
+            })
+            .collect()
+    }
+}
+
+/// Run the trace produced by `trace` once per [`WindowConfig`] in `matrix`. Screenshots taken with
+/// [`TraceEvent::TakeScreenshot`] and recordings are saved under a path suffixed by
+/// [`WindowConfig::label`]. `trace` is passed the configuration it runs in, so that a
+/// [`TraceEvent::CheckScreenshot`] can load the golden of that configuration with
+/// [`WindowConfig::golden_path`].
+///
+/// A failure in one configuration does not stop the others from running; every outcome is
+/// collected in the returned [`MatrixReport`]
+pub fn execute_iced_trace_matrix<A, T>(
+    flags: <A as iced::Application>::Flags,
+    trace: impl Fn(&WindowConfig) -> T,
+    matrix: &[WindowConfig],
+) -> MatrixReport<A>
+where
+    A: iced::Application + 'static,
+    A::Flags: Clone,
+    T: IntoIterator<Item = TraceEvent<A>>,
+{
+    Runner::new().execute_matrix(flags, trace, matrix)
+}
+
+/// Implements [`Runner::execute_matrix`]
+pub(super) fn run_matrix<A, T>(
+    runner: &Runner<A>,
+    flags: <A as iced::Application>::Flags,
+    trace: impl Fn(&WindowConfig) -> T,
+    matrix: &[WindowConfig],
+) -> MatrixReport<A>
+where
+    A: iced::Application + 'static,
+    A::Flags: Clone,
+    T: IntoIterator<Item = TraceEvent<A>>,
+{
+    let runs = matrix
+        .iter()
+        .map(|config| {
+            run_catching(
+                flags.clone(),
+                trace(config),
+                RunOptions {
+                    runner,
+                    window: config,
+                    golden_suffix: Some(run_label(config, None)),
+                    variant: None,
+                    adapter: RefCell::default(),
+                },
+            )
+        })
+        .collect();
+
+    MatrixReport { runs }
+}
+
+/// Run the trace produced by `trace` once per [`StyleVariant`] in the default [`WindowConfig`].
+///
+/// The variant is applied when the trace starts and again before every screenshot and layout
+/// check, so that a [`TraceEvent::SetState`] in the trace cannot change the style being tested.
+/// Screenshots taken with [`TraceEvent::TakeScreenshot`] and recordings are saved under a path
+/// suffixed by the [`MatrixRun::label`] of the run, e.g. `golden/main-800x800@1x-dark.png`.
+///
+/// A failure in one variant does not stop the others from running; every outcome is collected in
+/// the returned [`MatrixReport`]
+pub fn execute_iced_trace_variants<A, T>(
+    flags: <A as iced::Application>::Flags,
+    trace: impl Fn() -> T,
+    variants: &[StyleVariant<A>],
+) -> MatrixReport<A>
+where
+    A: iced::Application + 'static,
+    A::Flags: Clone,
+    T: IntoIterator<Item = TraceEvent<A>>,
+{
+    Runner::new().execute_variants(flags, trace, variants)
+}
+
+/// Implements [`Runner::execute_variants`]
+pub(super) fn run_variants<A, T>(
+    runner: &Runner<A>,
+    flags: <A as iced::Application>::Flags,
+    trace: impl Fn() -> T,
+    variants: &[StyleVariant<A>],
+) -> MatrixReport<A>
+where
+    A: iced::Application + 'static,
+    A::Flags: Clone,
+    T: IntoIterator<Item = TraceEvent<A>>,
+{
+    let runs = variants
+        .iter()
+        .map(|variant| {
+            run_catching(
+                flags.clone(),
+                trace(),
+                RunOptions {
+                    runner,
+                    window: &runner.window,
+                    golden_suffix: Some(run_label(&runner.window, Some(&variant.name))),
+                    variant: Some(variant),
+                    adapter: RefCell::default(),
+                },
+            )
+        })
+        .collect();
+
+    MatrixReport { runs }
+}
+
+/// Runs a trace, converting a failure into an error instead of unwinding
+fn run_catching<A>(
+    flags: <A as iced::Application>::Flags,
+    trace_events: impl IntoIterator<Item = TraceEvent<A>>,
+    options: RunOptions<'_, A>,
+) -> MatrixRun<A>
+where
+    A: iced::Application + 'static,
+{
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        run_trace(flags, trace_events, &options)
+    }))
+    .map_err(panic_message);
+
+    MatrixRun {
+        config: options.window.clone(),
+        variant: options.variant.map(|variant| variant.name.clone()),
+        result,
+        adapter: options.adapter.into_inner(),
+    }
+}
+
+/// Extracts the message from a panic payload
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| (*message).to_owned())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| String::from("unknown panic"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn golden_names() {
+        let path = Path::new("golden/main.png");
+        assert_eq!(
+            WindowConfig::new((390, 844))
+                .scale_factor(3.0)
+                .golden_path(path),
+            PathBuf::from("golden/main-390x844@3x.png")
+        );
+        assert_eq!(
+            WindowConfig::default().name("desktop").golden_path(path),
+            PathBuf::from("golden/main-desktop.png")
+        );
+    }
+
+    #[test]
+    fn variant_reports() {
+        let report: MatrixReport<()> = MatrixReport {
+            runs: vec![
+                MatrixRun {
+                    config: WindowConfig::default(),
+                    variant: Some(String::from("light")),
+                    result: Ok(()),
+                    adapter: None,
+                },
+                MatrixRun {
+                    config: WindowConfig::default(),
+                    variant: Some(String::from("dark")),
+                    result: Err(String::from("assertion failed")),
+                    adapter: None,
+                },
+            ],
+        };
+        assert!(!report.passed());
+        let failed: Vec<String> = report.failures().map(MatrixRun::label).collect();
+        assert_eq!(failed, vec![String::from("800x800@1x-dark")]);
+        assert_eq!(
+            suffixed_path(Path::new("golden/main.png"), &failed[0]),
+            PathBuf::from("golden/main-800x800@1x-dark.png")
+        );
+    }
+}
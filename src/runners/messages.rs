@@ -0,0 +1,358 @@
+//! Log of the messages delivered to an application during a trace
+//!
+//! The runner never polls [`Application::subscription`](iced::Application::subscription), so
+//! messages a subscription would produce are neither delivered nor logged; send them with
+//! [`TraceEvent::Message`](crate::TraceEvent::Message) instead
+use std::fmt;
+
+/// Where a delivered message came from. There is no source for subscriptions, which the runner
+/// does not poll
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MessageSource {
+    /// A [`TraceEvent::Message`](crate::TraceEvent::Message)
+    Trace,
+    /// The response of the [`CommandStub`](super::CommandStub) with this name
+    Stub(String),
+    /// A clipboard read requested in a command
+    Clipboard,
+    /// A widget reacting to simulated input
+    Widget,
+}
+
+impl fmt::Display for MessageSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Trace => write!(f, "trace"),
+            Self::Stub(name) => write!(f, "stub `{}`", name),
+            Self::Clipboard => write!(f, "clipboard"),
+            Self::Widget => write!(f, "widget"),
+        }
+    }
+}
+
+/// A message delivered to `update`
+#[derive(Debug, Clone)]
+pub struct LoggedMessage<M> {
+    /// Index of the [`TraceEvent`](crate::TraceEvent) during which the message was delivered,
+    /// `None` if it answered the command returned when the application was created
+    pub step: Option<usize>,
+    /// Where the message came from
+    pub source: MessageSource,
+    /// `Debug` output of the message
+    pub debug: String,
+    /// The message itself, if the runner keeps messages; see
+    /// [`Runner::keep_messages`](super::Runner::keep_messages)
+    pub message: Option<M>,
+}
+
+impl<M: fmt::Debug> LoggedMessage<M> {
+    /// Logs `message`, delivered from `source` during `step`, with the copy made by `keep`
+    // the Debug output is what failing expectations and message checks print; matching only
+    // uses the kept message
+    #[allow(clippy::use_debug)]
+    pub(crate) fn new(
+        step: Option<usize>,
+        source: MessageSource,
+        message: &M,
+        keep: Option<fn(&M) -> M>,
+    ) -> Self {
+        Self {
+            step,
+            source,
+            debug: format!("{:?}", message),
+            message: keep.map(|keep| keep(message)),
+        }
+    }
+}
+
+/// Every message delivered to an application during a trace, in delivery order. Subscriptions
+/// are not run, so none of their messages appear here
+///
+/// Messages that were not consumed by an [`Expectation`] yet are pending
+#[derive(Debug, Clone)]
+pub struct MessageLog<M> {
+    /// Delivered messages
+    entries: Vec<LoggedMessage<M>>,
+    /// Index of the first pending message
+    pending: usize,
+}
+
+impl<M> Default for MessageLog<M> {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            pending: 0,
+        }
+    }
+}
+
+/// Which pending messages an [`Expectation`] may match
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pending {
+    /// Only the oldest pending message
+    Next,
+    /// Any pending message; the messages before it are consumed too
+    Any,
+}
+
+/// Asserts that a pending message matches a pattern; built by [`expect_message!`]
+///
+/// Messages can only be matched if the runner keeps them, see
+/// [`Runner::keep_messages`](super::Runner::keep_messages)
+///
+/// [`expect_message!`]: crate::expect_message
+#[allow(missing_debug_implementations)]
+pub struct Expectation<M> {
+    /// Pattern as written, shown when the expectation fails
+    pub pattern: &'static str,
+    /// Which pending messages may match
+    pub pending: Pending,
+    /// Returns true if a message matches the pattern
+    pub matches: Box<dyn Fn(&M) -> bool>,
+}
+
+impl<M> Expectation<M> {
+    /// Create a new [`Expectation`] that `matches` the messages `pending` selects
+    pub fn new(
+        pattern: &'static str,
+        pending: Pending,
+        matches: impl Fn(&M) -> bool + 'static,
+    ) -> Self {
+        Self {
+            pattern,
+            pending,
+            matches: Box::new(matches),
+        }
+    }
+}
+
+impl<M> MessageLog<M> {
+    /// Every delivered message
+    #[must_use]
+    pub fn entries(&self) -> &[LoggedMessage<M>] {
+        &self.entries
+    }
+
+    /// Messages delivered during the [`TraceEvent`](crate::TraceEvent) at `step`
+    #[must_use]
+    pub fn at_step(&self, step: usize) -> &[LoggedMessage<M>] {
+        let start = self
+            .entries
+            .iter()
+            .position(|entry| entry.step == Some(step))
+            .unwrap_or(self.entries.len());
+        let len = self.entries[start..]
+            .iter()
+            .take_while(|entry| entry.step == Some(step))
+            .count();
+
+        &self.entries[start..start + len]
+    }
+
+    /// Messages delivered during the most recent step that delivered any
+    #[must_use]
+    pub fn last_step(&self) -> &[LoggedMessage<M>] {
+        match self.entries.last() {
+            Some(LoggedMessage {
+                step: Some(step), ..
+            }) => self.at_step(*step),
+            Some(_) => &self.entries,
+            None => &[],
+        }
+    }
+
+    /// Messages not consumed by an [`Expectation`] yet
+    #[must_use]
+    pub fn pending(&self) -> &[LoggedMessage<M>] {
+        &self.entries[self.pending..]
+    }
+
+    /// Consumes the pending messages up to the first one `expectation` matches. Fails with a
+    /// description of the pending messages if none matches
+    pub fn consume(&mut self, expectation: &Expectation<M>) -> Result<(), String> {
+        let candidates = match expectation.pending {
+            Pending::Next => self.pending().len().min(1),
+            Pending::Any => self.pending().len(),
+        };
+        let matched = self.pending()[..candidates].iter().position(|entry| {
+            entry
+                .message
+                .as_ref()
+                .map_or(false, |message| (expectation.matches)(message))
+        });
+        if let Some(position) = matched {
+            self.pending += position + 1;
+            return Ok(());
+        }
+
+        let expected = match expectation.pending {
+            Pending::Next => format!(
+                "expected the next message to match `{}`",
+                expectation.pattern
+            ),
+            Pending::Any => format!(
+                "expected a pending message to match `{}`",
+                expectation.pattern
+            ),
+        };
+        if self.pending().is_empty() {
+            return Err(format!("{}, but no messages are pending", expected));
+        }
+        let pending: Vec<&str> = self
+            .pending()
+            .iter()
+            .map(|entry| entry.debug.as_str())
+            .collect();
+        let hint = if self.pending().iter().any(|entry| entry.message.is_none()) {
+            "\n  messages are only matched if the runner keeps them, see `Runner::keep_messages`"
+        } else {
+            ""
+        };
+        Err(format!(
+            "{}, but the pending messages were:\n  {}{}",
+            expected,
+            pending.join("\n  "),
+            hint
+        ))
+    }
+
+    /// Messages delivered from `source`
+    pub fn from_source<'a>(
+        &'a self,
+        source: &'a MessageSource,
+    ) -> impl Iterator<Item = &'a LoggedMessage<M>> + 'a {
+        self.entries
+            .iter()
+            .filter(move |entry| &entry.source == source)
+    }
+
+    /// Returns true if exactly one message was delivered during `step` per matcher, each matching
+    /// the matcher at its position. Messages only match if the runner keeps them
+    #[must_use]
+    pub fn step_matches(&self, step: usize, matchers: &[&dyn Fn(&M) -> bool]) -> bool {
+        matches_exactly(self.at_step(step), matchers)
+    }
+
+    /// Logs a delivered message
+    pub(crate) fn push(&mut self, entry: LoggedMessage<M>) {
+        self.entries.push(entry);
+    }
+}
+
+/// Returns true if `entries` has one message per matcher, each matching the matcher at its
+/// position. Messages only match if the runner keeps them
+#[must_use]
+pub fn matches_exactly<M>(entries: &[LoggedMessage<M>], matchers: &[&dyn Fn(&M) -> bool]) -> bool {
+    entries.len() == matchers.len()
+        && entries.iter().zip(matchers).all(|(entry, matcher)| {
+            entry
+                .message
+                .as_ref()
+                .map_or(false, |message| matcher(message))
+        })
+}
+
+impl<M> fmt::Display for MessageLog<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.entries.is_empty() {
+            return write!(f, "  no messages were delivered");
+        }
+        for entry in &self.entries {
+            match entry.step {
+                Some(step) => write!(f, "  step {}", step)?,
+                None => write!(f, "  init")?,
+            }
+            writeln!(f, ", {}: {}", entry.source, entry.debug)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Builds a [`TraceEvent::ExpectMessage`](crate::TraceEvent::ExpectMessage) asserting that the
+/// next pending message matches a pattern, like [`matches!`]. Prefix the pattern with `any` to
+/// accept any pending message instead:
+///
+/// ```ignore
+/// expect_message!(Message::Saved { .. })
+/// expect_message!(any Message::Loaded(Ok(items)) if items.len() == 3)
+/// ```
+///
+/// Messages that were not matched are shown with their `Debug` output when the expectation fails.
+/// The runner must keep messages, see [`Runner::keep_messages`](crate::runners::Runner::keep_messages)
+#[macro_export]
+macro_rules! expect_message {
+    (any $pattern:pat $(if $guard:expr)? $(,)?) => {
+        $crate::TraceEvent::ExpectMessage($crate::runners::Expectation::new(
+            stringify!($pattern $(if $guard)?),
+            $crate::runners::Pending::Any,
+            move |message| matches!(message, $pattern $(if $guard)?),
+        ))
+    };
+    ($pattern:pat $(if $guard:expr)? $(,)?) => {
+        $crate::TraceEvent::ExpectMessage($crate::runners::Expectation::new(
+            stringify!($pattern $(if $guard)?),
+            $crate::runners::Pending::Next,
+            move |message| matches!(message, $pattern $(if $guard)?),
+        ))
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Logs `message` as delivered by the trace at `step`
+    fn logged(step: usize, message: i32) -> LoggedMessage<i32> {
+        LoggedMessage {
+            step: Some(step),
+            source: MessageSource::Trace,
+            debug: message.to_string(),
+            message: Some(message),
+        }
+    }
+
+    #[test]
+    fn steps_and_matchers() {
+        let mut log = MessageLog::default();
+        log.push(logged(0, 1));
+        log.push(logged(2, 2));
+        log.push(LoggedMessage {
+            source: MessageSource::Stub(String::from("save")),
+            ..logged(2, 3)
+        });
+
+        assert_eq!(log.at_step(1).len(), 0);
+        assert_eq!(log.at_step(2).len(), 2);
+        assert_eq!(log.last_step().len(), 2);
+        assert_eq!(log.from_source(&MessageSource::Trace).count(), 2);
+        assert!(log.step_matches(2, &[&|m| *m == 2, &|m| *m == 3]));
+        assert!(!log.step_matches(2, &[&|m| *m == 2]));
+        assert!(!log.step_matches(0, &[&|m| *m == 2]));
+        assert_eq!(
+            log.to_string(),
+            "  step 0, trace: 1\n  step 2, trace: 2\n  step 2, stub `save`: 3\n"
+        );
+    }
+
+    #[test]
+    fn expectations_consume_pending() {
+        let mut log = MessageLog::default();
+        log.push(logged(0, 1));
+        log.push(logged(0, 2));
+        log.push(logged(1, 3));
+        let next = |n: i32| Expectation::new("n", Pending::Next, move |m: &i32| *m == n);
+        let any = |n: i32| Expectation::new("n", Pending::Any, move |m: &i32| *m == n);
+
+        assert!(log.consume(&next(2)).is_err());
+        assert!(log.consume(&next(1)).is_ok());
+        assert!(log.consume(&any(3)).is_ok());
+        assert!(log.pending().is_empty());
+        assert_eq!(
+            log.consume(&any(3)),
+            Err(String::from(
+                "expected a pending message to match `n`, but no messages are pending"
+            ))
+        );
+    }
+}
@@ -0,0 +1,904 @@
+//! Interfaces for running applicationss end-to-end with no user interaction
+
+mod clipboard;
+mod commands;
+mod focus;
+mod input;
+mod matrix;
+mod messages;
+mod window;
+
+pub use commands::CommandStub;
+pub use focus::Focusable;
+pub use input::keys;
+pub use matrix::{
+    execute_iced_trace_matrix, execute_iced_trace_variants, MatrixReport, MatrixRun, StyleVariant,
+    WindowConfig,
+};
+pub use messages::{
+    matches_exactly, Expectation, LoggedMessage, MessageLog, MessageSource, Pending,
+};
+pub use window::{WindowChange, WindowHistory, WindowRecord};
+
+use crate::query::Selector;
+use crate::rendering::capture::{
+    dispatch_events, inspect_layout, locate_text_inputs, render_once, BACKEND,
+};
+use crate::rendering::recording::{Recorder, Recording};
+use crate::rendering::screenshot::{metadata, Screenshot};
+use crate::rendering::{CompositorError, HeadlessConfig};
+use crate::trace_events::{Mask, TraceEvent};
+use commands::CommandLoop;
+use iced_native::clipboard::Clipboard;
+use iced_native::keyboard::{KeyCode, Modifiers};
+use iced_native::mouse::ScrollDelta;
+use iced_native::Event;
+use matrix::suffixed_path;
+use std::cell::RefCell;
+use std::marker::PhantomData;
+
+/// Run an [`Application`] headlessly, discarding all commands with a series of [`TraceEvents`]. Returns the [`Application`] after
+/// all messages have been exhausted
+pub fn execute_iced_trace<A>(
+    flags: <A as iced::Application>::Flags,
+    trace_events: impl IntoIterator<Item = TraceEvent<A>>,
+) -> A
+where
+    A: iced::Application + 'static,
+{
+    Runner::new().execute(flags, trace_events)
+}
+
+/// Run an [`Application`] headlessly in a window described by [`WindowConfig`], discarding all
+/// commands with a series of [`TraceEvents`]. Returns the [`Application`] after all messages have
+/// been exhausted
+pub fn execute_iced_trace_with<A>(
+    flags: <A as iced::Application>::Flags,
+    trace_events: impl IntoIterator<Item = TraceEvent<A>>,
+    window: &WindowConfig,
+) -> A
+where
+    A: iced::Application + 'static,
+{
+    Runner::new()
+        .window(window.clone())
+        .execute(flags, trace_events)
+}
+
+/// Configurable runner for [`TraceEvent`]s
+///
+/// The `execute_*` functions in this module run traces with a default [`Runner`]
+#[allow(missing_debug_implementations)]
+pub struct Runner<A: iced::Application> {
+    /// Name of the trace, saved in the metadata of screenshots
+    name: Option<String>,
+    /// Window traces are executed in, unless overridden by a matrix
+    window: WindowConfig,
+    /// Animated recording of each trace, if any
+    recording: Option<Recording>,
+    /// Skip steps that render if no graphics adapter is available, instead of failing
+    skip_without_adapter: bool,
+    /// Selects the graphics adapter screenshots are rendered with
+    headless: HeadlessConfig,
+    /// Paint a marker at the virtual cursor into screenshots
+    show_cursor: bool,
+    /// Stand-ins for the futures of commands returned by the application
+    stubs: Vec<CommandStub<A>>,
+    /// What happens when the application asks to exit
+    on_exit: ExitPolicy,
+    /// Copies delivered messages into the message log, if set
+    keep_messages: Option<fn(&A::Message) -> A::Message>,
+    /// Text inputs focus moves between when Tab is pressed
+    focusables: Vec<Focusable<A>>,
+    /// The [`Application`] type traces are executed against
+    ///
+    /// [`Application`]: iced::Application
+    application: PhantomData<fn() -> A>,
+}
+
+impl<A: iced::Application> Default for Runner<A> {
+    fn default() -> Self {
+        Self {
+            name: None,
+            window: WindowConfig::default(),
+            recording: None,
+            skip_without_adapter: false,
+            headless: HeadlessConfig::default(),
+            show_cursor: false,
+            stubs: Vec::new(),
+            on_exit: ExitPolicy::default(),
+            keep_messages: None,
+            focusables: Vec::new(),
+            application: PhantomData,
+        }
+    }
+}
+
+impl<A> Runner<A>
+where
+    A: iced::Application + 'static,
+{
+    /// Create a new [`Runner`] with default settings
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the name of the trace, which is saved in the [`metadata`] of screenshots
+    ///
+    /// [`metadata`]: crate::rendering::screenshot::metadata
+    #[must_use]
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = Some(name.into());
+
+        self
+    }
+
+    /// Sets the window traces are executed in
+    #[must_use]
+    pub fn window(mut self, window: WindowConfig) -> Self {
+        self.window = window;
+
+        self
+    }
+
+    /// Records an animation of every trace executed by this runner
+    #[must_use]
+    pub fn record(mut self, recording: Recording) -> Self {
+        self.recording = Some(recording);
+
+        self
+    }
+
+    /// If set, [`TraceEvent::CheckScreenshot`], [`TraceEvent::TakeScreenshot`],
+    /// [`TraceEvent::LayoutCheck`] and recordings are skipped with a warning when rendering is
+    /// impossible on this machine, e.g. on a CI runner without a GPU. Other steps still run;
+    /// steps that pass input to widgets need a renderer to lay them out, so they fail instead.
+    ///
+    /// See [`CompositorError::is_unavailable`]
+    #[must_use]
+    pub const fn skip_without_adapter(mut self, skip: bool) -> Self {
+        self.skip_without_adapter = skip;
+
+        self
+    }
+
+    /// Sets the backends and adapter screenshots are rendered with. Defaults to
+    /// [`HeadlessConfig::from_env`]
+    #[must_use]
+    pub fn headless(mut self, config: HeadlessConfig) -> Self {
+        self.headless = config;
+
+        self
+    }
+
+    /// If set, screenshots and recordings show the virtual mouse cursor as a crosshair painted
+    /// with [`CURSOR_COLOR`](crate::rendering::screenshot::CURSOR_COLOR). The cursor is moved by
+    /// [`TraceEvent::MoveCursor`], [`TraceEvent::Hover`] and the events that click or scroll
+    #[must_use]
+    pub const fn show_cursor(mut self, show: bool) -> Self {
+        self.show_cursor = show;
+
+        self
+    }
+
+    /// Answers the commands matched by `stub` with its canned message instead of discarding
+    /// them; see [`CommandStub`]. A command is answered by the first registered stub that
+    /// matches it
+    #[must_use]
+    pub fn stub(mut self, stub: CommandStub<A>) -> Self {
+        self.stubs.push(stub);
+
+        self
+    }
+
+    /// Sets what happens when [`Application::should_exit`] returns true after a step; see
+    /// [`ExitPolicy`]
+    ///
+    /// [`Application::should_exit`]: iced::Application::should_exit
+    #[must_use]
+    pub const fn on_exit(mut self, policy: ExitPolicy) -> Self {
+        self.on_exit = policy;
+
+        self
+    }
+
+    /// Keeps a copy of every delivered message in the [`MessageLog`], so that checks can match
+    /// messages instead of their `Debug` output
+    #[must_use]
+    pub fn keep_messages(mut self) -> Self
+    where
+        A::Message: Clone,
+    {
+        self.keep_messages = Some(<A::Message as Clone>::clone);
+
+        self
+    }
+
+    /// Registers a text input that Tab and Shift+Tab move focus to; see [`Focusable`]
+    #[must_use]
+    pub fn focusable(mut self, focusable: Focusable<A>) -> Self {
+        self.focusables.push(focusable);
+
+        self
+    }
+
+    /// Runs the trace produced by `trace` once per [`WindowConfig`] in `matrix`; see
+    /// [`execute_iced_trace_matrix`]
+    pub fn execute_matrix<T>(
+        &self,
+        flags: <A as iced::Application>::Flags,
+        trace: impl Fn(&WindowConfig) -> T,
+        matrix: &[WindowConfig],
+    ) -> MatrixReport<A>
+    where
+        A::Flags: Clone,
+        T: IntoIterator<Item = TraceEvent<A>>,
+    {
+        matrix::run_matrix(self, flags, trace, matrix)
+    }
+
+    /// Runs the trace produced by `trace` once per [`StyleVariant`] in the window of this runner;
+    /// see [`execute_iced_trace_variants`]
+    pub fn execute_variants<T>(
+        &self,
+        flags: <A as iced::Application>::Flags,
+        trace: impl Fn() -> T,
+        variants: &[StyleVariant<A>],
+    ) -> MatrixReport<A>
+    where
+        A::Flags: Clone,
+        T: IntoIterator<Item = TraceEvent<A>>,
+    {
+        matrix::run_variants(self, flags, trace, variants)
+    }
+
+    /// Run an [`Application`] headlessly with a series of [`TraceEvents`], discarding commands
+    /// that no [`CommandStub`] answers. Returns the [`Application`] after all messages have been
+    /// exhausted
+    pub fn execute(
+        &self,
+        flags: <A as iced::Application>::Flags,
+        trace_events: impl IntoIterator<Item = TraceEvent<A>>,
+    ) -> A {
+        run_trace(
+            flags,
+            trace_events,
+            &RunOptions {
+                runner: self,
+                window: &self.window,
+                golden_suffix: None,
+                variant: None,
+                adapter: RefCell::default(),
+            },
+        )
+    }
+}
+
+/// What a [`Runner`] does when the application asks to exit, checked after every step
+///
+/// Trailing [`TraceEvent::AssertExited`] events are never considered remaining events
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitPolicy {
+    /// End the trace, skipping the remaining events
+    Stop,
+    /// End the trace, failing if any events remain
+    FailIfEventsRemain,
+    /// Keep executing the trace
+    Continue,
+}
+
+impl Default for ExitPolicy {
+    fn default() -> Self {
+        Self::Stop
+    }
+}
+
+/// Options of a single trace execution that are not exposed through [`TraceEvent`]s
+struct RunOptions<'a, A: iced::Application> {
+    /// Runner the trace is executed by
+    runner: &'a Runner<A>,
+    /// Window the trace starts in; window actions of the application resize it
+    window: &'a WindowConfig,
+    /// Appended to the file name of every screenshot and recording saved by the trace
+    golden_suffix: Option<String>,
+    /// Style applied when the trace starts and before every screenshot or layout check
+    variant: Option<&'a StyleVariant<A>>,
+    /// Adapter the last frame of the trace was rendered with; kept outside of the trace's
+    /// result so that it is reported when the trace panics
+    adapter: RefCell<Option<String>>,
+}
+
+impl<A: iced::Application> RunOptions<'_, A> {
+    /// Path a golden requested at `path` is saved to
+    fn golden_path(&self, path: &std::path::Path) -> std::path::PathBuf {
+        match &self.golden_suffix {
+            Some(suffix) => suffixed_path(path, suffix),
+            None => path.to_path_buf(),
+        }
+    }
+
+    /// Unwraps the result of rendering for `step`. Returns `None` if the step should be skipped
+    /// because rendering is unavailable, and panics on other failures
+    #[allow(clippy::panic, clippy::print_stderr)]
+    fn rendered<T>(&self, result: Result<T, CompositorError>, step: usize) -> Option<T> {
+        match result {
+            Ok(rendered) => Some(rendered),
+            Err(error) if self.runner.skip_without_adapter && error.is_unavailable() => {
+                eprintln!("warning: skipping step {}: {}", step, error);
+                None
+            }
+            Err(error) => panic!("Rendering step {} failed: {}", step, error),
+        }
+    }
+
+    /// Unwraps the rendering that dispatching input during `step` needs, and panics if it
+    /// failed. Unlike [`rendered`](Self::rendered) it never skips the step, since the trace
+    /// would go on without the messages the input produces
+    #[allow(clippy::panic)]
+    fn required<T>(&self, result: Result<T, CompositorError>, step: usize) -> T {
+        result.unwrap_or_else(|error| {
+            panic!(
+                "step {}: dispatching input needs a renderer to lay out widgets: {}",
+                step, error
+            )
+        })
+    }
+
+    /// Adds the trace name, `step` and style variant to the metadata of `screenshot`
+    fn describe(&self, screenshot: Screenshot, step: usize) -> Screenshot {
+        let mut screenshot = screenshot.with_metadata(metadata::STEP, step.to_string());
+        if let Some(name) = &self.runner.name {
+            screenshot = screenshot.with_metadata(metadata::TRACE, name.clone());
+        }
+        if let Some(variant) = self.variant {
+            screenshot = screenshot.with_metadata(metadata::VARIANT, variant.name.clone());
+        }
+
+        screenshot
+    }
+}
+
+/// Executes a trace as described by `options`
+#[allow(clippy::panic)]
+fn run_trace<A>(
+    flags: <A as iced::Application>::Flags,
+    trace_events: impl IntoIterator<Item = TraceEvent<A>>,
+    options: &RunOptions<'_, A>,
+) -> A
+where
+    A: iced::Application + 'static,
+{
+    let (raw_application, init_command) = A::new(flags);
+    let mut application = AppHarness(raw_application);
+    let mut commands = CommandLoop::new(&options.runner.stubs, options.runner.keep_messages);
+
+    let apply_variant = |application: &mut AppHarness<A>| {
+        if let Some(variant) = options.variant {
+            (variant.apply)(&mut application.0);
+        }
+    };
+    apply_variant(&mut application);
+    commands.init(&mut application, init_command);
+
+    let mut window = options.window.clone();
+    let mut window_history = WindowHistory::default();
+    window_history.observe(
+        None,
+        &application.0,
+        commands.take_window_actions(),
+        &mut window,
+    );
+
+    let mut masks: Vec<Mask> = Vec::new();
+    let mut cursor = input::CURSOR_OUTSIDE;
+    let mut recorder = options.runner.recording.clone().and_then(|recording| {
+        let first = options.rendered(
+            capture(&mut application, options, &window, &masks, cursor),
+            0,
+        )?;
+        let mut recorder = Recorder::new(recording);
+        recorder.start(|| first);
+        Some(recorder)
+    });
+
+    let mut events = trace_events.into_iter().enumerate();
+    for (step, event) in events.by_ref() {
+        let mut changes_state = matches!(
+            event,
+            TraceEvent::Message(_)
+                | TraceEvent::SetState(_)
+                | TraceEvent::KeyPress { .. }
+                | TraceEvent::TypeText { .. }
+                | TraceEvent::Scroll { .. }
+                | TraceEvent::ScrollIntoView { .. }
+                | TraceEvent::MoveCursor(_)
+                | TraceEvent::Hover(_)
+        );
+        match event {
+            TraceEvent::Message(message) => {
+                commands.update(&mut application, step, MessageSource::Trace, [message]);
+            }
+            TraceEvent::Duration(duration) => {
+                if let Some(recorder) = &mut recorder {
+                    recorder.advance(duration, || {
+                        capture(&mut application, options, &window, &masks, cursor)
+                            .expect("Rendering frame failed")
+                    });
+                }
+                changes_state = commands.advance(&mut application, step, duration);
+            }
+            TraceEvent::StateCheck(state_check) => {
+                assert!(state_check(&application.0));
+            }
+            TraceEvent::SetState(set_state) => {
+                set_state(&mut application.0);
+            }
+            TraceEvent::CheckScreenshot(screenshot_check) => {
+                apply_variant(&mut application);
+                let screenshot = capture(&mut application, options, &window, &masks, cursor);
+                if let Some(screenshot) = options.rendered(screenshot, step) {
+                    let screenshot = options.describe(screenshot, step);
+                    let environment = screenshot.environment();
+                    assert!(
+                        screenshot_check(screenshot),
+                        "screenshot check failed at step {}, rendered with {}; compare it to \
+                         the golden's environment with Screenshot::metadata_mismatch",
+                        step,
+                        environment
+                    );
+                }
+            }
+            TraceEvent::TakeScreenshot(path) => {
+                apply_variant(&mut application);
+                let screenshot = capture(&mut application, options, &window, &masks, cursor);
+                if let Some(screenshot) = options.rendered(screenshot, step) {
+                    options
+                        .describe(screenshot, step)
+                        .save_image_to_png(options.golden_path(&path))
+                        .expect("Saving screenshot failed");
+                }
+            }
+            TraceEvent::LayoutCheck(layout_check) => {
+                apply_variant(&mut application);
+                let layout = inspect_layout(&mut application, &window, &options.runner.headless);
+                if let Some(layout) = options.rendered(layout, step) {
+                    assert!(layout_check(&layout));
+                }
+            }
+            TraceEvent::Mask(new_masks) => {
+                masks = new_masks;
+            }
+            TraceEvent::SetClipboard(contents) => {
+                commands.clipboard.write(contents);
+            }
+            TraceEvent::ClipboardCheck(clipboard_check) => {
+                assert!(clipboard_check(commands.clipboard.contents()));
+            }
+            TraceEvent::WindowCheck(window_check) => {
+                assert!(window_check(&window_history));
+            }
+            TraceEvent::MessageCheck(message_check) => {
+                assert!(
+                    message_check(&commands.messages),
+                    "message check failed at step {}, delivered messages:\n{}",
+                    step,
+                    commands.messages
+                );
+            }
+            TraceEvent::ExpectMessage(expectation) => {
+                if let Err(failure) = commands.messages.consume(&expectation) {
+                    panic!("step {}: {}", step, failure);
+                }
+            }
+            TraceEvent::KeyPress {
+                key_code,
+                modifiers,
+            } => {
+                dispatch(
+                    &mut application,
+                    options,
+                    &window,
+                    &mut commands,
+                    step,
+                    &input::key_events(key_code, modifiers),
+                    cursor,
+                );
+
+                if key_code == KeyCode::Tab && !options.runner.focusables.is_empty() {
+                    let layout =
+                        inspect_layout(&mut application, &window, &options.runner.headless);
+                    let layout = options.required(layout, step);
+                    let order = focus::tab_order(&options.runner.focusables, &layout);
+                    focus::move_focus(&order, &mut application.0, modifiers.shift());
+                }
+            }
+            TraceEvent::TypeText { target, text } => {
+                let typing = input::typing_events(&text).unwrap_or_else(|character| {
+                    panic!(
+                        "step {}: cannot type control character U+{:04X}",
+                        step,
+                        u32::from(character)
+                    )
+                });
+                let layout = inspect_layout(&mut application, &window, &options.runner.headless);
+                cursor = options
+                    .required(layout, step)
+                    .bounds(&target)
+                    .unwrap_or_else(|| {
+                        panic!("step {}: no widget matches {} to type into", step, target)
+                    })
+                    .center();
+                let mut events = input::click_events(cursor);
+                events.extend(input::key_events(KeyCode::End, Modifiers::empty()));
+                events.extend(typing);
+                dispatch(
+                    &mut application,
+                    options,
+                    &window,
+                    &mut commands,
+                    step,
+                    &events,
+                    cursor,
+                );
+            }
+            TraceEvent::Scroll { target, delta } => {
+                let layout = inspect_layout(&mut application, &window, &options.runner.headless);
+                cursor = options
+                    .required(layout, step)
+                    .visible_bounds(&target)
+                    .unwrap_or_else(|| {
+                        panic!("step {}: no visible widget matches {}", step, target)
+                    })
+                    .center();
+                let mut events = input::move_events(cursor);
+                events.extend(input::scroll_events(delta));
+                dispatch(
+                    &mut application,
+                    options,
+                    &window,
+                    &mut commands,
+                    step,
+                    &events,
+                    cursor,
+                );
+            }
+            TraceEvent::ScrollIntoView {
+                target,
+                max_scrolls,
+            } => {
+                for scrolls in 0..=max_scrolls {
+                    let layout =
+                        inspect_layout(&mut application, &window, &options.runner.headless);
+                    let layout = options.required(layout, step);
+                    if layout.is_in_view(&target) {
+                        break;
+                    }
+                    assert!(
+                        scrolls < max_scrolls,
+                        "step {}: {} is not in view after {} scrolls",
+                        step,
+                        target,
+                        max_scrolls
+                    );
+                    let (bounds, viewport) = layout
+                        .bounds(&target)
+                        .zip(layout.clip(&target))
+                        .unwrap_or_else(|| {
+                            panic!(
+                                "step {}: no widget inside of a scrollable matches {}",
+                                step, target
+                            )
+                        });
+                    let lines = if bounds.y < viewport.y { 1.0 } else { -1.0 };
+                    cursor = viewport.center();
+                    let mut events = input::move_events(cursor);
+                    events.extend(input::scroll_events(ScrollDelta::Lines {
+                        x: 0.0,
+                        y: lines,
+                    }));
+                    dispatch(
+                        &mut application,
+                        options,
+                        &window,
+                        &mut commands,
+                        step,
+                        &events,
+                        cursor,
+                    );
+                }
+            }
+            TraceEvent::MoveCursor(position) => {
+                cursor = position;
+                dispatch(
+                    &mut application,
+                    options,
+                    &window,
+                    &mut commands,
+                    step,
+                    &input::move_events(cursor),
+                    cursor,
+                );
+            }
+            TraceEvent::Hover(target) => {
+                let layout = inspect_layout(&mut application, &window, &options.runner.headless);
+                cursor = options
+                    .required(layout, step)
+                    .visible_bounds(&target)
+                    .unwrap_or_else(|| {
+                        panic!("step {}: no visible widget matches {}", step, target)
+                    })
+                    .center();
+                dispatch(
+                    &mut application,
+                    options,
+                    &window,
+                    &mut commands,
+                    step,
+                    &input::move_events(cursor),
+                    cursor,
+                );
+            }
+            TraceEvent::FocusCheck(focus_check) => {
+                assert!(focus_check(focus::focused(
+                    &options.runner.focusables,
+                    &mut application.0
+                )));
+            }
+            TraceEvent::AssertFocusOrder(expected) => {
+                let layout = inspect_layout(&mut application, &window, &options.runner.headless);
+                let inputs =
+                    locate_text_inputs(&mut application, &window, &options.runner.headless);
+                if let (Some(layout), Some(inputs)) = (
+                    options.rendered(layout, step),
+                    options.rendered(inputs, step),
+                ) {
+                    let registered: Vec<_> = options
+                        .runner
+                        .focusables
+                        .iter()
+                        .filter_map(|focusable| layout.bounds(&focusable.selector))
+                        .collect();
+                    let unregistered: Vec<String> = inputs
+                        .iter()
+                        .filter(|point| !registered.iter().any(|bounds| bounds.contains(**point)))
+                        .map(|point| format!("({}, {})", point.x, point.y))
+                        .collect();
+                    assert!(
+                        unregistered.is_empty(),
+                        "text inputs at {} are not registered as Focusables, so the tab order \
+                         at step {} would skip them",
+                        unregistered.join(", "),
+                        step
+                    );
+
+                    let order: Vec<&Selector> =
+                        focus::tab_order(&options.runner.focusables, &layout)
+                            .into_iter()
+                            .map(|focusable| &focusable.selector)
+                            .collect();
+                    assert!(
+                        order.iter().copied().eq(expected.iter()),
+                        "tab order at step {} is [{}], expected [{}]",
+                        step,
+                        selector_list(order.iter().copied()),
+                        selector_list(&expected)
+                    );
+                }
+            }
+            TraceEvent::AssertExited => {
+                assert!(
+                    application.0.should_exit(),
+                    "application did not exit by step {}",
+                    step
+                );
+            }
+        }
+        window_history.observe(
+            Some(step),
+            &application.0,
+            commands.take_window_actions(),
+            &mut window,
+        );
+
+        if changes_state {
+            if let Some(recorder) = &mut recorder {
+                recorder.step(|| {
+                    capture(&mut application, options, &window, &masks, cursor)
+                        .expect("Rendering frame failed")
+                });
+            }
+        }
+
+        if options.runner.on_exit != ExitPolicy::Continue && application.0.should_exit() {
+            break;
+        }
+    }
+
+    let remaining = events
+        .filter(|(_, event)| !matches!(event, TraceEvent::AssertExited))
+        .count();
+    assert!(
+        !(options.runner.on_exit == ExitPolicy::FailIfEventsRemain && remaining > 0),
+        "application exited with {} trace events remaining",
+        remaining
+    );
+
+    if let (Some(recorder), Some(recording)) = (recorder, &options.runner.recording) {
+        recorder
+            .save(&options.golden_path(&recording.path))
+            .expect("Saving recording failed");
+    }
+    application.0
+}
+
+/// Lists `selectors` for a failure message, e.g. `path [1], path [2]`
+fn selector_list<'a>(selectors: impl IntoIterator<Item = &'a Selector>) -> String {
+    selectors
+        .into_iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Passes `events` to the widgets of the application in `window` with the cursor at
+/// `cursor_position`, and delivers the messages they produce during `step`
+fn dispatch<A>(
+    application: &mut AppHarness<A>,
+    options: &RunOptions<'_, A>,
+    window: &WindowConfig,
+    commands: &mut CommandLoop<'_, A>,
+    step: usize,
+    events: &[Event],
+    cursor_position: iced::Point,
+) where
+    A: iced::Application + 'static,
+{
+    let messages = dispatch_events(
+        application,
+        window,
+        &options.runner.headless,
+        events,
+        cursor_position,
+        &mut commands.clipboard,
+    );
+    let messages = options.required(messages, step);
+    commands.update(application, step, MessageSource::Widget, messages);
+}
+
+/// Renders a [`Screenshot`] of the application in `window` with the virtual cursor at `cursor`
+/// and `masks` painted out, and remembers the adapter it was rendered with
+fn capture<A>(
+    application: &mut AppHarness<A>,
+    options: &RunOptions<'_, A>,
+    window: &WindowConfig,
+    masks: &[Mask],
+    cursor: iced::Point,
+) -> Result<Screenshot, CompositorError>
+where
+    A: iced::Application + 'static,
+{
+    let config = &options.runner.headless;
+    let scale_factor = (window.scale_factor * application.0.scale_factor()) as f32;
+    let mut screenshot = render_once(application, window, config, cursor)?
+        .with_metadata(
+            metadata::WINDOW_SIZE,
+            format!("{}x{}", window.size.0, window.size.1),
+        )
+        .with_metadata(metadata::SCALE_FACTOR, window.scale_factor.to_string())
+        .with_metadata(metadata::BACKEND, BACKEND);
+    *options.adapter.borrow_mut() = screenshot.metadata().get(metadata::ADAPTER).cloned();
+    if options.runner.show_cursor && cursor.x >= 0.0 && cursor.y >= 0.0 {
+        screenshot = screenshot.paint_cursor(
+            (cursor.x * scale_factor) as u32,
+            (cursor.y * scale_factor) as u32,
+        );
+    }
+    if masks.is_empty() {
+        return Ok(screenshot);
+    }
+
+    let layout = masks
+        .iter()
+        .any(|mask| matches!(mask, Mask::Widget(_)))
+        .then(|| inspect_layout(application, window, config))
+        .transpose()?;
+
+    let regions: Vec<iced::Rectangle<u32>> = masks
+        .iter()
+        .flat_map(|mask| match mask {
+            Mask::Region(region) => vec![*region],
+            Mask::Widget(selector) => layout
+                .iter()
+                .flat_map(|layout| layout.all_bounds(selector))
+                .map(|bounds| {
+                    let x = (bounds.x * scale_factor).floor().max(0.0);
+                    let y = (bounds.y * scale_factor).floor().max(0.0);
+                    iced::Rectangle {
+                        x: x as u32,
+                        y: y as u32,
+                        width: ((bounds.x + bounds.width) * scale_factor).ceil().max(x) as u32
+                            - x as u32,
+                        height: ((bounds.y + bounds.height) * scale_factor).ceil().max(y) as u32
+                            - y as u32,
+                    }
+                })
+                .collect(),
+        })
+        .collect();
+
+    Ok(screenshot.mask(&regions))
+}
+
+/// Run an [`Application`] headlessly, discarding all commands with a series of [`TraceEvents`]. Returns the [`Application`] after
+/// all messages have been exhausted
+pub fn execute_message_trace<A>(
+    flags: A::Flags,
+    messages: impl IntoIterator<Item = A::Message>,
+) -> A
+where
+    A: iced::Application + 'static,
+{
+    let messages = messages.into_iter().map(TraceEvent::Message);
+    execute_iced_trace(flags, messages)
+}
+
+///Functionally, this is identical to the Instance type that exists in [`iced::application`] but is
+///not publically exposed. we use this as a "proxy" type to impl `crate::runtime::Application`.
+///
+///Maybe its better to expose the Instance type in iced to avoid repeated code
+#[repr(transparent)]
+struct AppHarness<A: iced::Application>(pub(crate) A);
+
+impl<A> crate::runtime::Program for AppHarness<A>
+where
+    A: iced::Application,
+{
+    type Renderer = crate::renderer::Renderer;
+    type Message = A::Message;
+
+    fn update(&mut self, message: Self::Message) -> crate::runtime::Command<Self::Message> {
+        self.0.update(message)
+    }
+
+    fn view(&mut self) -> crate::runtime::Element<'_, Self::Message, Self::Renderer> {
+        self.0.view()
+    }
+}
+
+impl<A> crate::runtime::Application for AppHarness<A>
+where
+    A: iced::Application,
+{
+    type Flags = A::Flags;
+
+    fn new(flags: Self::Flags) -> (Self, crate::runtime::Command<A::Message>) {
+        let (app, command) = A::new(flags);
+
+        (Self(app), command)
+    }
+
+    fn title(&self) -> String {
+        self.0.title()
+    }
+
+    fn mode(&self) -> crate::runtime::Mode {
+        crate::runtime::Mode::Hidden
+    }
+
+    fn subscription(&self) -> crate::runtime::Subscription<Self::Message> {
+        self.0.subscription()
+    }
+
+    fn background_color(&self) -> crate::runtime::Color {
+        self.0.background_color()
+    }
+
+    fn scale_factor(&self) -> f64 {
+        self.0.scale_factor()
+    }
+
+    fn should_exit(&self) -> bool {
+        self.0.should_exit()
+    }
+}
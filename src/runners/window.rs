@@ -0,0 +1,184 @@
+//! History of the window an application asked for
+use super::WindowConfig;
+use iced::window::Mode;
+use iced::Color;
+use iced_native::window;
+
+/// A change to its window requested by an application
+#[derive(Debug, Clone, PartialEq)]
+pub enum WindowChange {
+    /// Resize to a logical size, requested with a window action
+    Resize {
+        /// Logical width
+        width: u32,
+        /// Logical height
+        height: u32,
+    },
+    /// Move to a position on the screen, requested with a window action
+    Move {
+        /// Horizontal position in logical pixels
+        x: i32,
+        /// Vertical position in logical pixels
+        y: i32,
+    },
+    /// [`Application::mode`](iced::Application::mode) changed
+    Mode(Mode),
+    /// [`Application::title`](iced::Application::title) changed
+    Title(String),
+    /// [`Application::background_color`](iced::Application::background_color) changed
+    BackgroundColor(Color),
+}
+
+impl From<window::Action> for WindowChange {
+    fn from(action: window::Action) -> Self {
+        match action {
+            window::Action::Resize { width, height } => Self::Resize { width, height },
+            window::Action::Move { x, y } => Self::Move { x, y },
+        }
+    }
+}
+
+/// A [`WindowChange`] and when it happened
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowRecord {
+    /// Index of the [`TraceEvent`](crate::TraceEvent) that caused the change, `None` if the
+    /// window was set up when the application was created
+    pub step: Option<usize>,
+    /// The change
+    pub change: WindowChange,
+}
+
+/// Every change to its window an application requested during a trace
+///
+/// The headless window always stays hidden, whatever mode the application asks for; resizes are
+/// applied to the window screenshots are rendered in
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WindowHistory {
+    /// Changes in the order they happened
+    records: Vec<WindowRecord>,
+}
+
+impl WindowHistory {
+    /// Changes in the order they happened
+    #[must_use]
+    pub fn records(&self) -> &[WindowRecord] {
+        &self.records
+    }
+
+    /// Changes caused by the [`TraceEvent`](crate::TraceEvent) at `step`
+    pub fn at_step(&self, step: usize) -> impl Iterator<Item = &WindowChange> {
+        self.records
+            .iter()
+            .filter(move |record| record.step == Some(step))
+            .map(|record| &record.change)
+    }
+
+    /// Current title of the window
+    #[must_use]
+    pub fn title(&self) -> Option<&str> {
+        self.latest(|change| match change {
+            WindowChange::Title(title) => Some(title.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Current mode the application asks for
+    #[must_use]
+    pub fn mode(&self) -> Option<Mode> {
+        self.latest(|change| match change {
+            WindowChange::Mode(mode) => Some(*mode),
+            _ => None,
+        })
+    }
+
+    /// Current background color of the window
+    #[must_use]
+    pub fn background_color(&self) -> Option<Color> {
+        self.latest(|change| match change {
+            WindowChange::BackgroundColor(color) => Some(*color),
+            _ => None,
+        })
+    }
+
+    /// Logical size of the last requested resize, if any
+    #[must_use]
+    pub fn size(&self) -> Option<(u32, u32)> {
+        self.latest(|change| match change {
+            WindowChange::Resize { width, height } => Some((*width, *height)),
+            _ => None,
+        })
+    }
+
+    /// Most recent change selected by `select`
+    fn latest<'a, T>(&'a self, select: impl Fn(&'a WindowChange) -> Option<T>) -> Option<T> {
+        self.records
+            .iter()
+            .rev()
+            .find_map(|record| select(&record.change))
+    }
+
+    /// Records the window `actions` returned at `step`, and the title, mode and background color
+    /// of `application` if they changed. Resizes are applied to `window`
+    pub(crate) fn observe<A: iced::Application>(
+        &mut self,
+        step: Option<usize>,
+        application: &A,
+        actions: Vec<WindowChange>,
+        window: &mut WindowConfig,
+    ) {
+        let title = application.title();
+        let mode = application.mode();
+        let background_color = application.background_color();
+
+        let mut changes = actions;
+        if self.title() != Some(title.as_str()) {
+            changes.push(WindowChange::Title(title));
+        }
+        if self.mode() != Some(mode) {
+            changes.push(WindowChange::Mode(mode));
+        }
+        if self.background_color() != Some(background_color) {
+            changes.push(WindowChange::BackgroundColor(background_color));
+        }
+
+        for change in changes {
+            if let WindowChange::Resize { width, height } = change {
+                window.size = (width, height);
+            }
+            self.records.push(WindowRecord { step, change });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn latest_changes() {
+        let history = WindowHistory {
+            records: vec![
+                WindowRecord {
+                    step: None,
+                    change: WindowChange::Title(String::from("Counter")),
+                },
+                WindowRecord {
+                    step: Some(1),
+                    change: WindowChange::Resize {
+                        width: 300,
+                        height: 200,
+                    },
+                },
+                WindowRecord {
+                    step: Some(1),
+                    change: WindowChange::Title(String::from("Counter - 1")),
+                },
+            ],
+        };
+        assert_eq!(history.title(), Some("Counter - 1"));
+        assert_eq!(history.size(), Some((300, 200)));
+        assert_eq!(history.mode(), None);
+        assert_eq!(history.at_step(1).count(), 2);
+        assert_eq!(history.at_step(0).count(), 0);
+    }
+}
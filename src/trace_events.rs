@@ -4,13 +4,21 @@
 //! [`Application`]: iced::Application
 
 //pub use iced::Screenshot;
+use crate::query::{LayoutSnapshot, Selector};
 use crate::rendering::screenshot::Screenshot;
+use crate::runners::{Expectation, MessageLog, WindowHistory};
+use iced::{Point, Rectangle};
+use iced_native::keyboard::{KeyCode, Modifiers};
+use iced_native::mouse::ScrollDelta;
 use std::path::PathBuf;
 /// Events that can compose serialized execution
 pub enum TraceEvent<A: iced::Application> {
     /// Send a message directly to the application's uppdate fn
     Message(A::Message),
-    /// Delay for [`Duration`]
+    /// Advance the trace's virtual clock by [`Duration`], delivering the responses of delayed
+    /// [`CommandStub`]s that became due; the trace does not sleep
+    ///
+    /// [`CommandStub`]: crate::runners::CommandStub
     Duration(std::time::Duration),
     ///Check [`Application`] state -- allow the end user to embed assertions in the trace
     StateCheck(Box<dyn Fn(&A) -> bool>),
@@ -21,4 +29,150 @@ pub enum TraceEvent<A: iced::Application> {
     CheckScreenshot(Box<dyn Fn(Screenshot) -> bool>),
     ///Take a screenshot, save it to the provided [`PathBuf`]
     TakeScreenshot(PathBuf),
+    ///Lay out the current app, passes the resulting [`LayoutSnapshot`] into a user provided
+    ///function
+    LayoutCheck(Box<dyn Fn(&LayoutSnapshot) -> bool>),
+    ///Mask regions of every following screenshot, replacing masks set by an earlier event. Masked
+    ///regions are painted out in saved screenshots and ignored when screenshots are compared
+    Mask(Vec<Mask>),
+    ///Write to the trace's virtual clipboard, which widgets and clipboard commands read from
+    SetClipboard(String),
+    ///Check the contents of the trace's virtual clipboard, `None` if nothing was written
+    ClipboardCheck(Box<dyn Fn(Option<&str>) -> bool>),
+    ///Check the window actions, title, mode and background color the application requested so far
+    WindowCheck(Box<dyn Fn(&WindowHistory) -> bool>),
+    ///Check the messages delivered to the application so far; failures print the log
+    MessageCheck(Box<dyn Fn(&MessageLog<A::Message>) -> bool>),
+    ///Assert that a pending message matches a pattern, consuming it; built with
+    ///[`expect_message!`](crate::expect_message)
+    ExpectMessage(Expectation<A::Message>),
+    ///Press and release a key; the widgets of the application receive the key events. Tab and
+    ///Shift+Tab also move focus between the runner's [`Focusable`]s
+    ///
+    ///[`Focusable`]: crate::runners::Focusable
+    KeyPress {
+        /// The key
+        key_code: KeyCode,
+        /// Modifiers held while the key is pressed
+        modifiers: Modifiers,
+    },
+    ///Click the text input matched by `target`, move its cursor to the end and type `text`; the
+    ///messages the input produces are delivered to the application. The control characters in
+    ///[`keys`] type Backspace, Delete, Enter and the select all, copy, cut and paste shortcuts,
+    ///which use the trace's virtual clipboard
+    ///
+    ///[`keys`]: crate::runners::keys
+    TypeText {
+        /// Matches the input or its text; an empty input only draws its placeholder
+        target: Selector,
+        /// The text to type
+        text: String,
+    },
+    ///Scroll the mouse wheel over the visible part of the widget matched by `target`, e.g. a
+    ///scrollable or a widget inside of it
+    Scroll {
+        /// Matches the widget the cursor is placed over
+        target: Selector,
+        /// How far the wheel is scrolled; negative lines scroll down
+        delta: ScrollDelta,
+    },
+    ///Scroll the scrollable the widget matched by `target` is in, one line at a time, until the
+    ///widget lies inside of its viewport and the window. Fails if it is not in view after
+    ///`max_scrolls` scrolls
+    ScrollIntoView {
+        /// Matches the widget to bring into view
+        target: Selector,
+        /// Scrolls after which the event fails
+        max_scrolls: usize,
+    },
+    ///Move the virtual mouse cursor to a position in logical pixels. Widgets receive the move,
+    ///and following screenshots draw the widgets under the cursor hovered
+    MoveCursor(Point),
+    ///Move the virtual mouse cursor to the center of the visible part of the widget matched by a
+    ///[`Selector`]
+    Hover(Selector),
+    ///Check the [`Selector`] of the focused [`Focusable`], `None` if no input is focused
+    ///
+    ///[`Focusable`]: crate::runners::Focusable
+    FocusCheck(Box<dyn Fn(Option<&Selector>) -> bool>),
+    ///Assert the order Tab moves focus in, given by the layout of the runner's [`Focusable`]s.
+    ///Fails if the layout holds a text input that is not registered as a [`Focusable`]
+    ///
+    ///[`Focusable`]: crate::runners::Focusable
+    AssertFocusOrder(Vec<Selector>),
+    ///Assert that the application asked to exit through [`Application::should_exit`]
+    ///
+    ///[`Application::should_exit`]: iced::Application::should_exit
+    AssertExited,
+}
+
+impl<A: iced::Application> TraceEvent<A> {
+    /// Press and release `key_code` without modifiers
+    #[must_use]
+    pub fn key(key_code: KeyCode) -> Self {
+        Self::KeyPress {
+            key_code,
+            modifiers: Modifiers::empty(),
+        }
+    }
+
+    /// Click the text input matched by `target` and type `text`
+    #[must_use]
+    pub fn type_text<S: Into<String>>(target: Selector, text: S) -> Self {
+        Self::TypeText {
+            target,
+            text: text.into(),
+        }
+    }
+
+    /// Scroll down by `lines` over the widget matched by `target`
+    #[must_use]
+    pub fn scroll_down(target: Selector, lines: f32) -> Self {
+        Self::Scroll {
+            target,
+            delta: ScrollDelta::Lines { x: 0.0, y: -lines },
+        }
+    }
+
+    /// Scroll up by `lines` over the widget matched by `target`
+    #[must_use]
+    pub fn scroll_up(target: Selector, lines: f32) -> Self {
+        Self::Scroll {
+            target,
+            delta: ScrollDelta::Lines { x: 0.0, y: lines },
+        }
+    }
+
+    /// Scroll until the widget matched by `target` is in view, failing after 100 scrolls
+    #[must_use]
+    pub fn scroll_into_view(target: Selector) -> Self {
+        Self::ScrollIntoView {
+            target,
+            max_scrolls: 100,
+        }
+    }
+
+    /// Press Tab, moving focus to the next input
+    #[must_use]
+    pub fn tab() -> Self {
+        Self::key(KeyCode::Tab)
+    }
+
+    /// Press Shift+Tab, moving focus to the previous input
+    #[must_use]
+    pub fn shift_tab() -> Self {
+        Self::KeyPress {
+            key_code: KeyCode::Tab,
+            modifiers: Modifiers::SHIFT,
+        }
+    }
+}
+
+/// A region of a [`Screenshot`] that is painted out before it is checked or saved
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mask {
+    /// A region measured in physical pixels
+    Region(Rectangle<u32>),
+    /// The bounds of the widget matched by a [`Selector`], resolved when the screenshot is taken
+    Widget(Selector),
 }